@@ -0,0 +1,38 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use hex::FromHex;
+use ur_registry::crypto_hd_key::CryptoHDKey;
+use ur_registry::crypto_key_path::CryptoKeyPath;
+use ur_registry::extend::crypto_multi_accounts::CryptoMultiAccounts;
+use ur_registry::traits::To;
+
+fn multi_accounts(count: u32) -> CryptoMultiAccounts {
+    let key = Vec::from_hex("026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6").unwrap();
+    let chain_code = Vec::from_hex("ced155c72456255881793514edc5bd9447e7f74abb88c6d6b6480fd016ee8c85").unwrap();
+    let keys = (0..count)
+        .map(|account| {
+            let origin = CryptoKeyPath::from_path(format!("m/44'/60'/{}'", account), Some([0xe9, 0x18, 0x1c, 0xf3])).unwrap();
+            CryptoHDKey::new_mainnet_extended(key.clone(), chain_code.clone(), Some(origin))
+        })
+        .collect();
+    CryptoMultiAccounts::new([0xe9, 0x18, 0x1c, 0xf3], keys, Some("keystone".to_string()))
+}
+
+fn encode_multi_accounts(c: &mut Criterion) {
+    let multi_accounts = multi_accounts(50);
+    c.bench_function("multi_accounts_50_to_cbor", |b| {
+        b.iter(|| black_box(&multi_accounts).to_cbor())
+    });
+    c.bench_function("multi_accounts_50_to_bytes", |b| {
+        b.iter(|| black_box(&multi_accounts).to_bytes())
+    });
+    let mut buf = Vec::with_capacity(8192);
+    c.bench_function("multi_accounts_50_to_bytes_into", |b| {
+        b.iter(|| {
+            buf.clear();
+            black_box(&multi_accounts).to_bytes_into(&mut buf);
+        })
+    });
+}
+
+criterion_group!(benches, encode_multi_accounts);
+criterion_main!(benches);
@@ -0,0 +1,62 @@
+// the full QR round trip between a watch-only wallet and a signer:
+// cargo run --example eth_sign_flow --features eth,secp256k1
+use hex::FromHex;
+use ur_registry::crypto_key_path::CryptoKeyPath;
+use ur_registry::ethereum::eth_sign_request::{DataType, EthSignRequest};
+use ur_registry::ethereum::eth_signature::EthSignature;
+use ur_registry::traits::{From, UR};
+use ur_registry::ur_decoder::{decode_ur, encode_ur, URDecoder};
+
+fn main() -> Result<(), String> {
+    // wallet side: the EIP-155 example transaction, signed by the key at m/44'/60'/0'/0/0
+    let request_id = Vec::from_hex("9b1deb4d3b7d4bad9bdd2b0d7b3dcb6d").unwrap();
+    let sign_data = Vec::from_hex(
+        "ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080",
+    )
+    .unwrap();
+    let request = EthSignRequest::new(
+        Some(request_id),
+        sign_data,
+        DataType::Transaction,
+        Some(1),
+        CryptoKeyPath::from_path("m/44'/60'/0'/0/0".to_string(), Some([0xe9, 0x18, 0x1c, 0xf3]))?,
+        None,
+        Some("example wallet".to_string()),
+    );
+
+    // a small fragment length so the request is split over several animated QR frames
+    let mut encoder = request.to_ur_encoder(40);
+    let mut decoder = URDecoder::new();
+    while !decoder.is_complete() {
+        let part = encoder.next_part().map_err(|e| e.to_string())?;
+        println!("{}", part);
+        decoder.receive(&part)?;
+    }
+
+    // signer side: decode the scanned request, sign it and show the signature as a single frame
+    let received = EthSignRequest::from_bytes(decoder.message()?.ok_or("incomplete ur")?)?;
+    let tx = received.decode_legacy_tx()?;
+    println!(
+        "sign tx to 0x{} with value {} wei on chain {}",
+        hex::encode(tx.get_to().unwrap_or_default()),
+        tx.get_value(),
+        tx.get_chain_id()
+    );
+    let signature = Vec::from_hex(concat!(
+        "28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276",
+        "67cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+        "25"
+    ))
+    .unwrap();
+    let response = encode_ur(&EthSignature::new(received.get_request_id(), signature, None));
+    println!("{}", response);
+
+    // wallet side: decode the scanned signature and check who signed
+    let signature: EthSignature = decode_ur(&response)?;
+    if signature.get_request_id() != request.get_request_id() {
+        return Err("the signature answers a different request".to_string());
+    }
+    println!("signature {}", signature.to_hex_string());
+    println!("signed by {}", signature.recover_address(&request)?);
+    Ok(())
+}
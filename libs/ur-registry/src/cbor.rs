@@ -0,0 +1,449 @@
+// the registry types only talk to the cbor backend through this module. `Value` is owned by the
+// crate and converted at the encode/decode boundary, so neither serde_cbor nor ciborium shows up in
+// the public api and either backend can be compiled out
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+#[cfg(not(any(feature = "serde_cbor", feature = "ciborium")))]
+compile_error!("one of the serde_cbor or ciborium features is required as cbor backend");
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Integer(i128),
+    Float(f64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Value>),
+    Map(BTreeMap<Value, Value>),
+    Tag(u64, Box<Value>),
+}
+
+impl Value {
+    fn major_type(&self) -> u8 {
+        match self {
+            Value::Integer(x) if *x < 0 => 1,
+            Value::Integer(_) => 0,
+            Value::Bytes(_) => 2,
+            Value::Text(_) => 3,
+            Value::Array(_) => 4,
+            Value::Map(_) => 5,
+            Value::Tag(_, _) => 6,
+            Value::Null | Value::Bool(_) | Value::Float(_) => 7,
+        }
+    }
+}
+
+// the canonical cbor order serde_cbor uses, map keys are encoded in this order so the bytes of
+// both backends match: major type, then length, then integer magnitude or content, and the
+// encoding itself for everything else
+impl Ord for Value {
+    fn cmp(&self, other: &Value) -> Ordering {
+        if self.major_type() != other.major_type() {
+            return self.major_type().cmp(&other.major_type());
+        }
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a.abs().cmp(&b.abs()),
+            (Value::Bytes(a), Value::Bytes(b)) if a.len() != b.len() => a.len().cmp(&b.len()),
+            (Value::Text(a), Value::Text(b)) if a.len() != b.len() => a.len().cmp(&b.len()),
+            (Value::Array(a), Value::Array(b)) if a.len() != b.len() => a.len().cmp(&b.len()),
+            (Value::Map(a), Value::Map(b)) if a.len() != b.len() => a.len().cmp(&b.len()),
+            (Value::Bytes(a), Value::Bytes(b)) => a.cmp(b),
+            (Value::Text(a), Value::Text(b)) => a.cmp(b),
+            (a, b) => encode(a).unwrap_or_default().cmp(&encode(b).unwrap_or_default()),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+#[cfg(not(feature = "ciborium"))]
+pub fn encode(value: &Value) -> Result<Vec<u8>, String> {
+    serde_cbor::to_vec(&serde_cbor_backend::to_serde_cbor(value)).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "ciborium"))]
+pub fn encode_to_writer<W: Write>(writer: &mut W, value: &Value) -> Result<(), String> {
+    serde_cbor::to_writer(writer, &serde_cbor_backend::to_serde_cbor(value)).map_err(|e| e.to_string())
+}
+
+// deepest legitimate registry item (multi-accounts -> hdkey -> keypath) nests well below this
+pub const MAX_DEPTH: usize = 32;
+
+pub fn decode(bytes: &[u8]) -> Result<Value, String> {
+    decode_with_max_depth(bytes, MAX_DEPTH)
+}
+
+// the nesting is checked on the raw bytes first, the backends decode recursively and would
+// overflow the stack before a check on the decoded value could run
+pub fn decode_with_max_depth(bytes: &[u8], max_depth: usize) -> Result<Value, String> {
+    skip_item(bytes, 0, max_depth, false)?;
+    decode_value(bytes)
+}
+
+// arrays, maps and tags count as one level each, the walk stops as soon as the limit is passed
+pub fn check_depth(value: &Value, max_depth: usize) -> Result<(), String> {
+    let children: Vec<&Value> = match value {
+        Value::Array(x) => x.iter().collect(),
+        Value::Map(x) => x.iter().flat_map(|(k, v)| [k, v]).collect(),
+        Value::Tag(_, x) => vec![x.as_ref()],
+        _ => return Ok(()),
+    };
+    if max_depth == 0 {
+        return Err("cbor nesting exceeds the maximum depth".to_string());
+    }
+    children
+        .into_iter()
+        .try_for_each(|child| check_depth(child, max_depth - 1))
+}
+
+// the decoded maps are BTreeMaps where a repeated key silently replaces the earlier one, so
+// duplicates are looked for in the raw items before decoding
+pub fn decode_strict(bytes: &[u8]) -> Result<Value, String> {
+    check_duplicate_keys(bytes)?;
+    decode(bytes)
+}
+
+pub fn check_duplicate_keys(bytes: &[u8]) -> Result<(), String> {
+    let end = skip_item(bytes, 0, MAX_DEPTH, true)?;
+    if end != bytes.len() {
+        return Err(format!("unexpected trailing data after cbor item at offset {}", end));
+    }
+    Ok(())
+}
+
+const BREAK: u8 = 0xff;
+
+// returns the offset right after the item starting at `offset`, with `check_keys` maps are also
+// checked for repeated keys
+fn skip_item(bytes: &[u8], offset: usize, max_depth: usize, check_keys: bool) -> Result<usize, String> {
+    let major_type = byte_at(bytes, offset)? >> 5;
+    let (length, mut offset) = read_argument(bytes, offset)?;
+    if matches!(major_type, 4 | 5 | 6) && max_depth == 0 {
+        return Err("cbor nesting exceeds the maximum depth".to_string());
+    }
+    match (major_type, length) {
+        (0 | 1 | 7, _) => Ok(offset),
+        (2 | 3, Some(length)) => usize::try_from(length)
+            .ok()
+            .and_then(|length| offset.checked_add(length))
+            .filter(|end| *end <= bytes.len())
+            .ok_or(format!("cbor data is truncated at byte {}", bytes.len())),
+        (2 | 3 | 4, None) => {
+            while byte_at(bytes, offset)? != BREAK {
+                offset = skip_item(bytes, offset, max_depth.saturating_sub(1), check_keys)?;
+            }
+            Ok(offset + 1)
+        }
+        (4, Some(length)) => {
+            for _ in 0..length {
+                offset = skip_item(bytes, offset, max_depth - 1, check_keys)?;
+            }
+            Ok(offset)
+        }
+        (5, length) => {
+            let mut keys = std::collections::BTreeSet::new();
+            let mut remaining = length;
+            loop {
+                match remaining {
+                    Some(0) => return Ok(offset),
+                    Some(x) => remaining = Some(x - 1),
+                    None if byte_at(bytes, offset)? == BREAK => return Ok(offset + 1),
+                    None => {}
+                }
+                let key_start = offset;
+                offset = skip_item(bytes, offset, max_depth - 1, check_keys)?;
+                if check_keys {
+                    let key = decode_value(&bytes[key_start..offset])?;
+                    if keys.contains(&key) {
+                        return Err(format!("duplicate key in cbor map: {:?}", key));
+                    }
+                    keys.insert(key);
+                }
+                offset = skip_item(bytes, offset, max_depth - 1, check_keys)?;
+            }
+        }
+        (6, _) => skip_item(bytes, offset, max_depth - 1, check_keys),
+        _ => Err(format!("invalid cbor major type {}", major_type)),
+    }
+}
+
+fn byte_at(bytes: &[u8], offset: usize) -> Result<u8, String> {
+    bytes
+        .get(offset)
+        .copied()
+        .ok_or(format!("cbor data is truncated at byte {}", offset))
+}
+
+// the length or value in the head of the item, None for indefinite length items
+fn read_argument(bytes: &[u8], offset: usize) -> Result<(Option<u64>, usize), String> {
+    let initial = byte_at(bytes, offset)?;
+    let size = match initial & 0x1f {
+        x @ 0..=23 => return Ok((Some(x as u64), offset + 1)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        31 if matches!(initial >> 5, 2..=5) => return Ok((None, offset + 1)),
+        _ => return Err(format!("invalid cbor initial byte {:#04x}", initial)),
+    };
+    let argument = bytes
+        .get(offset + 1..offset + 1 + size)
+        .ok_or(format!("cbor data is truncated at byte {}", bytes.len()))?
+        .iter()
+        .fold(0u64, |argument, byte| (argument << 8) | *byte as u64);
+    Ok((Some(argument), offset + 1 + size))
+}
+
+// a partial QR scan shows up as truncated data, the offset tells how far the payload got
+#[cfg(not(feature = "ciborium"))]
+fn decode_value(bytes: &[u8]) -> Result<Value, String> {
+    let value: serde_cbor::Value = serde_cbor::from_slice(bytes).map_err(|e| match e.is_eof() {
+        true => format!("cbor data is truncated at byte {}", e.offset()),
+        false => e.to_string(),
+    })?;
+    serde_cbor_backend::from_serde_cbor(value)
+}
+
+// unused when ciborium is the backend but still compiled, the backend comparison test needs it
+#[cfg(feature = "serde_cbor")]
+#[cfg_attr(feature = "ciborium", allow(dead_code))]
+mod serde_cbor_backend {
+    use crate::cbor::Value;
+    use serde_cbor::Value as SerdeCborValue;
+
+    pub fn to_serde_cbor(value: &Value) -> SerdeCborValue {
+        match value {
+            Value::Null => SerdeCborValue::Null,
+            Value::Bool(x) => SerdeCborValue::Bool(*x),
+            Value::Integer(x) => SerdeCborValue::Integer(*x),
+            Value::Float(x) => SerdeCborValue::Float(*x),
+            Value::Bytes(x) => SerdeCborValue::Bytes(x.clone()),
+            Value::Text(x) => SerdeCborValue::Text(x.clone()),
+            Value::Array(x) => SerdeCborValue::Array(x.iter().map(to_serde_cbor).collect()),
+            Value::Map(x) => SerdeCborValue::Map(
+                x.iter()
+                    .map(|(k, v)| (to_serde_cbor(k), to_serde_cbor(v)))
+                    .collect(),
+            ),
+            Value::Tag(tag, x) => SerdeCborValue::Tag(*tag, Box::new(to_serde_cbor(x))),
+        }
+    }
+
+    pub fn from_serde_cbor(value: SerdeCborValue) -> Result<Value, String> {
+        Ok(match value {
+            SerdeCborValue::Null => Value::Null,
+            SerdeCborValue::Bool(x) => Value::Bool(x),
+            SerdeCborValue::Integer(x) => Value::Integer(x),
+            SerdeCborValue::Float(x) => Value::Float(x),
+            SerdeCborValue::Bytes(x) => Value::Bytes(x),
+            SerdeCborValue::Text(x) => Value::Text(x),
+            SerdeCborValue::Array(x) => Value::Array(
+                x.into_iter().map(from_serde_cbor).collect::<Result<Vec<Value>, String>>()?,
+            ),
+            SerdeCborValue::Map(x) => Value::Map(
+                x.into_iter()
+                    .map(|(k, v)| Ok((from_serde_cbor(k)?, from_serde_cbor(v)?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+            SerdeCborValue::Tag(tag, x) => Value::Tag(tag, Box::new(from_serde_cbor(*x)?)),
+            x => return Err(format!("unsupported cbor value: {:?}", x)),
+        })
+    }
+}
+
+#[cfg(feature = "ciborium")]
+pub fn encode(value: &Value) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    encode_to_writer(&mut bytes, value)?;
+    Ok(bytes)
+}
+
+#[cfg(feature = "ciborium")]
+pub fn encode_to_writer<W: Write>(writer: &mut W, value: &Value) -> Result<(), String> {
+    ciborium::ser::into_writer(&ciborium_backend::to_ciborium(value)?, writer).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "ciborium")]
+fn decode_value(bytes: &[u8]) -> Result<Value, String> {
+    // from_reader stops after the first item, the reader is left at the end of it
+    let mut reader = bytes;
+    let value: ciborium::value::Value = ciborium::de::from_reader(&mut reader).map_err(|e| match e {
+        ciborium::de::Error::Io(_) => format!("cbor data is truncated at byte {}", bytes.len() - reader.len()),
+        ciborium::de::Error::Syntax(offset) => format!("invalid cbor at offset {}", offset),
+        e => e.to_string(),
+    })?;
+    if !reader.is_empty() {
+        return Err(format!(
+            "unexpected trailing data after cbor item at offset {}",
+            bytes.len() - reader.len()
+        ));
+    }
+    ciborium_backend::from_ciborium(value)
+}
+
+#[cfg(feature = "ciborium")]
+mod ciborium_backend {
+    use crate::cbor::Value;
+    use ciborium::value::{Integer, Value as CiboriumValue};
+
+    // map entries are written in BTreeMap order, which is the canonical order serde_cbor produces
+    pub fn to_ciborium(value: &Value) -> Result<CiboriumValue, String> {
+        Ok(match value {
+            Value::Null => CiboriumValue::Null,
+            Value::Bool(x) => CiboriumValue::Bool(*x),
+            Value::Integer(x) => CiboriumValue::Integer(
+                Integer::try_from(*x).map_err(|_| format!("integer out of range for cbor: {}", x))?,
+            ),
+            Value::Float(x) => CiboriumValue::Float(*x),
+            Value::Bytes(x) => CiboriumValue::Bytes(x.clone()),
+            Value::Text(x) => CiboriumValue::Text(x.clone()),
+            Value::Array(x) => CiboriumValue::Array(
+                x.iter().map(to_ciborium).collect::<Result<Vec<CiboriumValue>, String>>()?,
+            ),
+            Value::Map(x) => CiboriumValue::Map(
+                x.iter()
+                    .map(|(k, v)| Ok((to_ciborium(k)?, to_ciborium(v)?)))
+                    .collect::<Result<Vec<(CiboriumValue, CiboriumValue)>, String>>()?,
+            ),
+            Value::Tag(tag, x) => CiboriumValue::Tag(*tag, Box::new(to_ciborium(x)?)),
+        })
+    }
+
+    pub fn from_ciborium(value: CiboriumValue) -> Result<Value, String> {
+        Ok(match value {
+            CiboriumValue::Null => Value::Null,
+            CiboriumValue::Bool(x) => Value::Bool(x),
+            CiboriumValue::Integer(x) => Value::Integer(i128::from(x)),
+            CiboriumValue::Float(x) => Value::Float(x),
+            CiboriumValue::Bytes(x) => Value::Bytes(x),
+            CiboriumValue::Text(x) => Value::Text(x),
+            CiboriumValue::Array(x) => Value::Array(
+                x.into_iter().map(from_ciborium).collect::<Result<Vec<Value>, String>>()?,
+            ),
+            CiboriumValue::Map(x) => Value::Map(
+                x.into_iter()
+                    .map(|(k, v)| Ok((from_ciborium(k)?, from_ciborium(v)?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+            CiboriumValue::Tag(tag, x) => Value::Tag(tag, Box::new(from_ciborium(*x)?)),
+            x => return Err(format!("unsupported cbor value: {:?}", x)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cbor::{decode, decode_strict, decode_with_max_depth, encode, Value};
+    use crate::traits::From;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_encode_decode() {
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(2), Value::Integer(1));
+        map.insert(Value::Integer(1), Value::Integer(0));
+        let bytes = encode(&Value::Map(map.clone())).unwrap();
+        assert_eq!("a201000201", hex::encode(&bytes));
+        assert_eq!(Value::Map(map), decode(&bytes).unwrap());
+        assert!(decode(&[0xa2, 0x01]).is_err());
+    }
+
+    #[test]
+    fn test_canonical_key_order() {
+        // unsigned before negative before text, whatever the insertion order
+        let map = [
+            (Value::Text("a".to_string()), Value::Integer(0)),
+            (Value::Integer(-1), Value::Integer(0)),
+            (Value::Integer(1), Value::Integer(0)),
+        ];
+        let bytes = encode(&Value::Map(map.into_iter().collect())).unwrap();
+        assert_eq!("a301002000616100", hex::encode(&bytes));
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        use hex::FromHex;
+        let bytes = Vec::from_hex("a5035821026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6045820ced155c72456255881793514edc5bd9447e7f74abb88c6d6b6480fd016ee8c8505d90131a1020106d90130a1018a182cf501f501f500f401f4081ae9181cf3").unwrap();
+        let error = crate::crypto_hd_key::CryptoHDKey::from_bytes(bytes[..50].to_vec()).unwrap_err();
+        let offset = error
+            .strip_prefix("cbor data is truncated at byte ")
+            .and_then(|offset| offset.parse::<usize>().ok())
+            .unwrap();
+        assert!(offset > 0 && offset <= 50);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        // 40 nested single element arrays around 0
+        let mut bytes = vec![0x81; 40];
+        bytes.push(0x00);
+        assert_eq!(
+            "cbor nesting exceeds the maximum depth",
+            decode(&bytes).unwrap_err()
+        );
+        assert!(decode_with_max_depth(&bytes, 40).is_ok());
+        assert!(decode_with_max_depth(&bytes, 39).is_err());
+    }
+
+    #[test]
+    fn test_max_depth_before_decoding() {
+        // far deeper than the backends can recurse, rejected before they see it
+        let mut bytes = vec![0x81; 100_000];
+        bytes.push(0x00);
+        assert_eq!(
+            "cbor nesting exceeds the maximum depth",
+            decode(&bytes).unwrap_err()
+        );
+        let bytes = vec![0xc6; 100_000];
+        assert!(decode(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_decode_strict() {
+        // {1: 0, 2: 1} and {1: 0, 1: 1}
+        assert!(decode_strict(&[0xa2, 0x01, 0x00, 0x02, 0x01]).is_ok());
+        assert_eq!(
+            "duplicate key in cbor map: Integer(1)",
+            decode_strict(&[0xa2, 0x01, 0x00, 0x01, 0x01]).unwrap_err()
+        );
+        assert!(decode(&[0xa2, 0x01, 0x00, 0x01, 0x01]).is_ok());
+        // the same key written as 1 and as 0x1801, and inside an indefinite length map in an array
+        assert!(decode_strict(&[0xa2, 0x01, 0x00, 0x18, 0x01, 0x01]).is_err());
+        assert!(decode_strict(&[0x81, 0xbf, 0x01, 0x00, 0x01, 0x01, 0xff]).is_err());
+        assert!(decode_strict(&[0xa2, 0x01]).is_err());
+        assert!(decode_strict(&[0xa1, 0x01, 0x00, 0x00]).is_err());
+    }
+
+    #[cfg(all(feature = "ciborium", feature = "serde_cbor"))]
+    #[test]
+    fn test_ciborium_matches_serde_cbor() {
+        use crate::cbor::serde_cbor_backend::{from_serde_cbor, to_serde_cbor};
+        use hex::FromHex;
+        let vectors = [
+            "a5035821026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6045820ced155c72456255881793514edc5bd9447e7f74abb88c6d6b6480fd016ee8c8505d90131a1020106d90130a1018a182cf501f501f500f401f4081ae9181cf3",
+            "a3011ae9181cf30281d9012fa203582102eae4b876a8696134b868f88cc2f51f715f2dbedb7446b8e6edf3d4541c4eb67b06d90130a10188182cf51901f5f500f500f503686b657973746f6e65",
+        ];
+        for vector in vectors {
+            let bytes = Vec::from_hex(vector).unwrap();
+            let value = decode(&bytes).unwrap();
+            assert_eq!(from_serde_cbor(serde_cbor::from_slice(&bytes).unwrap()).unwrap(), value);
+            assert_eq!(serde_cbor::to_vec(&to_serde_cbor(&value)).unwrap(), encode(&value).unwrap());
+            assert_eq!(bytes, encode(&value).unwrap());
+        }
+    }
+}
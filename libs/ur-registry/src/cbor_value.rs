@@ -1,6 +1,10 @@
+use crate::registry_types::normalize_tag;
+use crate::traits::RegistryItem;
 use crate::types::Bytes;
-use serde_cbor::Value;
+use crate::cbor::Value;
+use hex::FromHex;
 use std::collections::BTreeMap;
+use std::io::Write;
 
 pub struct CborValue {
     value: Value,
@@ -53,6 +57,15 @@ impl CborValue {
         }
     }
 
+    // some producers write byte fields as hex text, only lenient decoders accept that
+    pub fn get_bytes_or_hex(&self) -> Result<Bytes, String> {
+        match &self.value {
+            Value::Text(x) => Vec::from_hex(x)
+                .map_err(|e| format!("invalid hex text when decoding Value::Bytes: {}", e)),
+            _ => self.get_bytes(),
+        }
+    }
+
     pub fn get_bool(&self) -> Result<bool, String> {
         match self.value.clone() {
             Value::Bool(value) => Ok(value),
@@ -101,7 +114,7 @@ impl CborValue {
     pub fn get_tag(&self, tag: u64) -> Result<CborValue, String> {
         match self.value.clone() {
             Value::Tag(x, y) => {
-                if x != tag {
+                if normalize_tag(x) != tag {
                     Err(format!(
                         "unexpected tag when decoding Value::Tag: received: {:?}, expected: {:?}",
                         x, tag
@@ -114,3 +127,89 @@ impl CborValue {
         }
     }
 }
+
+pub const MAJOR_TYPE_ARRAY: u8 = 4;
+pub const MAJOR_TYPE_MAP: u8 = 5;
+
+// writes the head of a definite length item, used when encoding containers incrementally
+pub fn write_header<W: Write>(writer: &mut W, major_type: u8, length: u64) -> Result<(), String> {
+    let major_type = major_type << 5;
+    let result = match length {
+        0..=23 => writer.write_all(&[major_type | length as u8]),
+        24..=0xff => writer.write_all(&[major_type | 24, length as u8]),
+        0x100..=0xffff => writer
+            .write_all(&[major_type | 25])
+            .and_then(|_| writer.write_all(&(length as u16).to_be_bytes())),
+        0x10000..=0xffff_ffff => writer
+            .write_all(&[major_type | 26])
+            .and_then(|_| writer.write_all(&(length as u32).to_be_bytes())),
+        _ => writer
+            .write_all(&[major_type | 27])
+            .and_then(|_| writer.write_all(&length.to_be_bytes())),
+    };
+    result.map_err(|e| e.to_string())
+}
+
+// passes the error through unchanged, with the log feature it is also emitted at debug level
+// together with the registry type so bad QR codes can be diagnosed in the field
+pub fn log_decode_error<T: RegistryItem>(error: String) -> String {
+    #[cfg(feature = "log")]
+    log::debug!("failed to decode {}: {}", T::get_registry_type().get_type_str(), error);
+    error
+}
+
+// same as `log_decode_error` for an error in one of the map's fields, the key is logged too
+pub fn log_field_error<T: RegistryItem>(key: i128) -> impl Fn(String) -> String {
+    move |error| {
+        #[cfg(feature = "log")]
+        log::debug!("failed to decode {} field {}: {}", T::get_registry_type().get_type_str(), key, error);
+        #[cfg(not(feature = "log"))]
+        let _ = key;
+        error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_log_decode_error() {
+        use crate::crypto_coin_info::CryptoCoinInfo;
+        use crate::crypto_hd_key::CryptoHDKey;
+        use crate::traits::From;
+        use hex::FromHex;
+        use std::sync::Mutex;
+
+        struct TestLogger(Mutex<Vec<String>>);
+        impl log::Log for TestLogger {
+            fn enabled(&self, metadata: &log::Metadata) -> bool {
+                metadata.level() <= log::Level::Debug
+            }
+            fn log(&self, record: &log::Record) {
+                self.0.lock().unwrap().push(record.args().to_string());
+            }
+            fn flush(&self) {}
+        }
+        static LOGGER: TestLogger = TestLogger(Mutex::new(Vec::new()));
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+
+        // {4: h'00'}, the key data is missing
+        let error = CryptoHDKey::from_bytes(Vec::from_hex("a1044100").unwrap()).unwrap_err();
+        assert_eq!("key data is required for crypto-hdkey", error);
+        // {1: 2^32}, errors from `from_cbor` are logged as well
+        let value = crate::cbor::decode(&Vec::from_hex("a1011b0000000100000000").unwrap()).unwrap();
+        let error = CryptoCoinInfo::try_from(value).unwrap_err();
+        assert_eq!("coin type out of range: 4294967296", error);
+        // a bare true, not a map
+        assert!(CryptoCoinInfo::from_cbor(crate::cbor::Value::Bool(true)).is_err());
+        assert_eq!(
+            vec![
+                "failed to decode crypto-hdkey field 3: key data is required for crypto-hdkey".to_string(),
+                "failed to decode crypto-coin-info field 1: coin type out of range: 4294967296".to_string(),
+                "failed to decode crypto-coin-info: unexpected data when decoding Value::Map: Bool(true)".to_string(),
+            ],
+            *LOGGER.0.lock().unwrap()
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use crate::crypto::bip32::hash160;
+use crate::crypto::secp::context;
+use bech32::{u5, ToBase32, Variant};
+use secp256k1::{PublicKey, Scalar};
+use sha2::{Digest, Sha256};
+
+pub struct AddressParams {
+    pub p2pkh_version: u8,
+    pub p2sh_version: u8,
+    pub bech32_hrp: &'static str,
+}
+
+pub const BITCOIN_MAINNET: AddressParams = AddressParams {
+    p2pkh_version: 0x00,
+    p2sh_version: 0x05,
+    bech32_hrp: "bc",
+};
+
+pub const BITCOIN_TESTNET: AddressParams = AddressParams {
+    p2pkh_version: 0x6f,
+    p2sh_version: 0xc4,
+    bech32_hrp: "tb",
+};
+
+pub fn p2pkh(public_key: &PublicKey, params: &AddressParams) -> String {
+    base58_address(params.p2pkh_version, &hash160(&public_key.serialize()))
+}
+
+pub fn p2sh_p2wpkh(public_key: &PublicKey, params: &AddressParams) -> String {
+    let mut redeem_script = vec![0x00, 0x14];
+    redeem_script.extend(hash160(&public_key.serialize()));
+    base58_address(params.p2sh_version, &hash160(&redeem_script))
+}
+
+pub fn p2wpkh(public_key: &PublicKey, params: &AddressParams) -> Result<String, String> {
+    segwit_address(params.bech32_hrp, 0, &hash160(&public_key.serialize()))
+}
+
+// key path only spend as in BIP86, the internal key is tweaked with an empty script tree
+pub fn p2tr(public_key: &PublicKey, params: &AddressParams) -> Result<String, String> {
+    let (internal_key, _) = public_key.x_only_public_key();
+    let tag = Sha256::digest(b"TapTweak");
+    let tweak: [u8; 32] = Sha256::new()
+        .chain_update(tag)
+        .chain_update(tag)
+        .chain_update(internal_key.serialize())
+        .finalize()
+        .into();
+    let tweak = Scalar::from_be_bytes(tweak).map_err(|_| "invalid taproot tweak".to_string())?;
+    let (output_key, _) = internal_key
+        .add_tweak(context(), &tweak)
+        .map_err(|e| format!("invalid taproot tweak: {}", e))?;
+    segwit_address(params.bech32_hrp, 1, &output_key.serialize())
+}
+
+fn base58_address(version: u8, hash: &[u8]) -> String {
+    let mut payload = vec![version];
+    payload.extend(hash);
+    bs58::encode(payload).with_check().into_string()
+}
+
+fn segwit_address(hrp: &str, version: u8, program: &[u8]) -> Result<String, String> {
+    let mut data = vec![u5::try_from_u8(version).map_err(|e| e.to_string())?];
+    data.extend(program.to_base32());
+    let variant = match version {
+        0 => Variant::Bech32,
+        _ => Variant::Bech32m,
+    };
+    bech32::encode(hrp, data, variant).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::address::{p2pkh, p2sh_p2wpkh, p2tr, BITCOIN_MAINNET};
+    use crate::crypto::secp::public_key;
+    use hex::FromHex;
+
+    #[test]
+    fn test_addresses() {
+        let key = public_key(
+            &Vec::from_hex("035a784662a4a20a65bf6aab9ae98a6c068a81c52e4b032c0fb5400c706cfccc56").unwrap(),
+        )
+        .unwrap();
+        assert_eq!("19Q2WoS5hSS6T8GjhK8KZLMgmWaq4neXrh", p2pkh(&key, &BITCOIN_MAINNET));
+        assert_eq!("3AbBmNbPDSzeZKHywDrH3h5v2rL8xGfT7e", p2sh_p2wpkh(&key, &BITCOIN_MAINNET));
+
+        // BIP86 first receive address of the "abandon ... about" mnemonic
+        let key = public_key(
+            &Vec::from_hex("03cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+            p2tr(&key, &BITCOIN_MAINNET).unwrap()
+        );
+    }
+}
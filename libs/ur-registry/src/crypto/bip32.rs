@@ -0,0 +1,104 @@
+use crate::crypto::secp::context;
+use crate::crypto_key_path::PathComponent;
+use crate::types::Fingerprint;
+use hmac::{Hmac, Mac};
+use ripemd::Ripemd160;
+use secp256k1::{PublicKey, Scalar, SecretKey};
+use sha2::{Digest, Sha256, Sha512};
+
+pub fn hash160(data: &[u8]) -> [u8; 20] {
+    Ripemd160::digest(Sha256::digest(data)).into()
+}
+
+pub fn fingerprint(public_key: &[u8]) -> Fingerprint {
+    let hash = hash160(public_key);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+pub fn derive_private_child(
+    key: &SecretKey,
+    chain_code: &[u8],
+    index: u32,
+) -> Result<(SecretKey, Vec<u8>), String> {
+    let mut mac = Hmac::<Sha512>::new_from_slice(chain_code).map_err(|e| e.to_string())?;
+    if index & PathComponent::HARDEN_BIT != 0 {
+        mac.update(&[0]);
+        mac.update(&key.secret_bytes());
+    } else {
+        mac.update(&PublicKey::from_secret_key(context(), key).serialize());
+    }
+    mac.update(&index.to_be_bytes());
+    let result = mac.finalize().into_bytes();
+    let mut tweak = [0u8; 32];
+    tweak.copy_from_slice(&result[..32]);
+    let tweak = Scalar::from_be_bytes(tweak)
+        .map_err(|_| format!("invalid child key at index {}", index))?;
+    let child = key
+        .add_tweak(&tweak)
+        .map_err(|_| format!("invalid child key at index {}", index))?;
+    Ok((child, result[32..].to_vec()))
+}
+
+pub fn derive_public_child(
+    key: &PublicKey,
+    chain_code: &[u8],
+    index: u32,
+) -> Result<(PublicKey, Vec<u8>), String> {
+    if index & PathComponent::HARDEN_BIT != 0 {
+        return Err(format!("cannot derive hardened index {} from a public key", index));
+    }
+    let mut mac = Hmac::<Sha512>::new_from_slice(chain_code).map_err(|e| e.to_string())?;
+    mac.update(&key.serialize());
+    mac.update(&index.to_be_bytes());
+    let result = mac.finalize().into_bytes();
+    let mut tweak = [0u8; 32];
+    tweak.copy_from_slice(&result[..32]);
+    let tweak = Scalar::from_be_bytes(tweak)
+        .map_err(|_| format!("invalid child key at index {}", index))?;
+    let child = key
+        .add_exp_tweak(context(), &tweak)
+        .map_err(|_| format!("invalid child key at index {}", index))?;
+    Ok((child, result[32..].to_vec()))
+}
+
+pub fn derive_private_path(
+    key: &SecretKey,
+    chain_code: &[u8],
+    indexes: &[u32],
+) -> Result<(SecretKey, Vec<u8>), String> {
+    indexes
+        .iter()
+        .try_fold((*key, chain_code.to_vec()), |(key, chain_code), index| {
+            derive_private_child(&key, &chain_code, *index)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::bip32::{derive_private_path, fingerprint};
+    use crate::crypto::secp::{public_key_from_private, secret_key};
+    use hex::FromHex;
+
+    #[test]
+    fn test_derive_private_path() {
+        let key = secret_key(
+            &Vec::from_hex("e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35").unwrap(),
+        )
+        .unwrap();
+        let chain_code =
+            Vec::from_hex("873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508").unwrap();
+        assert_eq!(
+            "3442193e",
+            hex::encode(fingerprint(&public_key_from_private(&key.secret_bytes()).unwrap()))
+        );
+        let (child, child_chain_code) = derive_private_path(&key, &chain_code, &[0x80000000]).unwrap();
+        assert_eq!(
+            "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea",
+            hex::encode(child.secret_bytes())
+        );
+        assert_eq!(
+            "47fdacbd0f1097043b78c63c20c34ef4ed9a111d980047ad16282c7ae6236141",
+            hex::encode(child_chain_code)
+        );
+    }
+}
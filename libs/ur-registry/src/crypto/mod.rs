@@ -0,0 +1,6 @@
+#[cfg(feature = "secp256k1")]
+pub mod address;
+#[cfg(feature = "secp256k1")]
+pub mod bip32;
+#[cfg(feature = "secp256k1")]
+pub mod secp;
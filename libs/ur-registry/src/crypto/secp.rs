@@ -0,0 +1,63 @@
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{All, Message, PublicKey, Secp256k1, SecretKey};
+use std::sync::OnceLock;
+
+static CONTEXT: OnceLock<Secp256k1<All>> = OnceLock::new();
+
+pub fn context() -> &'static Secp256k1<All> {
+    CONTEXT.get_or_init(Secp256k1::new)
+}
+
+// hd keys store private keys with a leading zero byte, both forms are accepted here
+pub fn secret_key(private_key: &[u8]) -> Result<SecretKey, String> {
+    let key = match private_key {
+        [0, key @ ..] if key.len() == 32 => key,
+        key => key,
+    };
+    SecretKey::from_slice(key).map_err(|e| format!("invalid private key: {}", e))
+}
+
+pub fn public_key(key_data: &[u8]) -> Result<PublicKey, String> {
+    PublicKey::from_slice(key_data).map_err(|e| format!("invalid public key: {}", e))
+}
+
+pub fn public_key_from_private(private_key: &[u8]) -> Result<[u8; 33], String> {
+    let secret_key = secret_key(private_key)?;
+    Ok(PublicKey::from_secret_key(context(), &secret_key).serialize())
+}
+
+pub fn recover_public_key(
+    message_hash: &[u8],
+    signature: &[u8],
+    recovery_id: i32,
+) -> Result<PublicKey, String> {
+    let message = Message::from_slice(message_hash).map_err(|e| format!("invalid message hash: {}", e))?;
+    let recovery_id = RecoveryId::from_i32(recovery_id).map_err(|e| format!("invalid recovery id: {}", e))?;
+    let signature = RecoverableSignature::from_compact(signature, recovery_id)
+        .map_err(|e| format!("invalid signature: {}", e))?;
+    context()
+        .recover_ecdsa(&message, &signature)
+        .map_err(|e| format!("unable to recover public key: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::secp::public_key_from_private;
+    use hex::FromHex;
+
+    #[test]
+    fn test_public_key_from_private() {
+        let private_key =
+            Vec::from_hex("00e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35")
+                .unwrap();
+        assert_eq!(
+            "0339a36013301597daef41fbe593a02cc513d0b55527ec2df1050e2e8ff49c85c2",
+            hex::encode(public_key_from_private(&private_key).unwrap())
+        );
+        assert_eq!(
+            public_key_from_private(&private_key).unwrap(),
+            public_key_from_private(&private_key[1..]).unwrap()
+        );
+        assert!(public_key_from_private(&[0u8; 32]).is_err());
+    }
+}
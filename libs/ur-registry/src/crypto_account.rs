@@ -1,7 +1,15 @@
 use std::collections::BTreeMap;
-use serde_cbor::{from_slice, to_vec, Value};
-use crate::cbor_value::CborValue;
+use std::io::Write;
+use crate::cbor::{decode, encode, encode_to_writer, Value};
+use crate::cbor_value::{log_decode_error, log_field_error, write_header, CborValue, MAJOR_TYPE_ARRAY, MAJOR_TYPE_MAP};
+use crate::crypto_key_path::CryptoKeyPath;
 use crate::crypto_output::CryptoOutput;
+#[cfg(feature = "coldcard")]
+use crate::ext_key_version::ScriptType;
+#[cfg(feature = "coldcard")]
+use serde_json::{json, Map};
+use crate::extend::crypto_multi_accounts::CryptoMultiAccounts;
+use crate::script_expression::ScriptExpression;
 use crate::registry_types::{CRYPTO_ACCOUNT, RegistryType};
 use crate::traits::{RegistryItem, To, From};
 use crate::types::{Fingerprint};
@@ -44,6 +52,143 @@ impl CryptoAccount {
     pub fn set_output_descriptors(&mut self, outputs: Vec<CryptoOutput>) {
         self.output_descriptors = outputs;
     }
+
+    // keys of single-key descriptors only, outputs using multi, sortedmulti, raw or addr expressions are skipped
+    pub fn to_multi_accounts(&self, device: Option<String>) -> CryptoMultiAccounts {
+        let keys = self
+            .output_descriptors
+            .iter()
+            .filter(|output| {
+                !output.get_script_expressions().iter().any(|expression| {
+                    matches!(
+                        expression,
+                        ScriptExpression::MultiSig
+                            | ScriptExpression::SortedMultiSig
+                            | ScriptExpression::RawScript
+                            | ScriptExpression::Address
+                    )
+                })
+            })
+            .map(|output| output.get_crypto_key())
+            .collect();
+        CryptoMultiAccounts::new(self.master_fingerprint, keys, device)
+    }
+
+    // origin of each descriptor's key in descriptor order, keys without an origin are left out
+    pub fn derivation_paths(&self) -> Vec<CryptoKeyPath> {
+        self.output_descriptors
+            .iter()
+            .filter_map(|output| output.get_crypto_key().get_origin())
+            .collect()
+    }
+
+    // one checksummed descriptor per line, in descriptor order, for pasting into a wallet's import box
+    pub fn to_descriptors_text(&self) -> String {
+        self.output_descriptors
+            .iter()
+            .map(|output| output.to_descriptor_string_with_checksum())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // Coldcard's generic JSON export. like Coldcard it describes a single account, a later descriptor
+    // with an already exported layout is skipped and so are layouts Coldcard doesn't list. the
+    // master xpub isn't part of crypto-account so the top level `xpub` is left out
+    #[cfg(feature = "coldcard")]
+    pub fn to_coldcard_json(&self) -> Result<String, String> {
+        let xfp = hex::encode_upper(self.master_fingerprint);
+        let first_key = self.output_descriptors.first().map(|output| output.get_crypto_key());
+        let chain = match first_key.as_ref().map_or(false, |key| key.is_testnet()) {
+            true => "XTN",
+            false => "BTC",
+        };
+        // serde_json keeps the insertion order with preserve_order, the same order Coldcard writes
+        let mut fields = Map::new();
+        fields.insert("chain".to_string(), json!(chain));
+        fields.insert("xfp".to_string(), json!(xfp));
+        if let Some(account) = first_key.and_then(|key| key.get_account_index(2)) {
+            fields.insert("account".to_string(), json!(account));
+        }
+        let mut sections = vec![];
+        for output in &self.output_descriptors {
+            let (section, name, script_type) = match coldcard_section(&output.get_script_expressions()) {
+                Some(x) if !sections.contains(&x.0) => x,
+                _ => continue,
+            };
+            sections.push(section);
+            let key = output.get_crypto_key();
+            let deriv = key
+                .get_origin()
+                .and_then(|origin| origin.get_path())
+                .map_or("m".to_string(), |path| format!("m/{}", path));
+            let mut entries = Map::new();
+            entries.insert("name".to_string(), json!(name));
+            entries.insert("xfp".to_string(), json!(xfp));
+            entries.insert("deriv".to_string(), json!(deriv));
+            entries.insert("xpub".to_string(), json!(key.get_bip32_key()));
+            if let Some(script_type) = script_type {
+                entries.insert("_pub".to_string(), json!(key.get_slip132_key(script_type)?));
+            }
+            fields.insert(section.to_string(), entries.into());
+        }
+        serde_json::to_string(&fields).map_err(|e| e.to_string())
+    }
+
+    // produces the same bytes as `to_bytes` but only builds the Value of one descriptor at a time
+    pub fn to_writer<W: Write>(&self, writer: &mut W) -> Result<(), String> {
+        write_header(writer, MAJOR_TYPE_MAP, 2)?;
+        encode_to_writer(writer, &Value::Integer(MASTER_FINGERPRINT))?;
+        encode_to_writer(writer, &Value::Integer(u32::from_be_bytes(self.master_fingerprint) as i128))?;
+        encode_to_writer(writer, &Value::Integer(OUTPUT_DESCRIPTORS))?;
+        write_header(writer, MAJOR_TYPE_ARRAY, self.output_descriptors.len() as u64)?;
+        self.output_descriptors
+            .iter()
+            .try_for_each(|output| encode_to_writer(writer, &output.to_cbor()))
+    }
+}
+
+// section, script name and SLIP-132 script type of the `_pub` entry for each layout of Coldcard's export
+#[cfg(feature = "coldcard")]
+fn coldcard_section(
+    script_expressions: &[ScriptExpression],
+) -> Option<(&'static str, &'static str, Option<ScriptType>)> {
+    let is_multisig = |expression: &ScriptExpression| {
+        matches!(expression, ScriptExpression::MultiSig | ScriptExpression::SortedMultiSig)
+    };
+    match script_expressions {
+        [ScriptExpression::PublicKeyHash] => Some(("bip44", "p2pkh", None)),
+        [ScriptExpression::ScriptHash, ScriptExpression::WitnessPublicKeyHash] => {
+            Some(("bip49", "p2sh-p2wpkh", Some(ScriptType::P2shP2wpkh)))
+        }
+        [ScriptExpression::WitnessPublicKeyHash] => Some(("bip84", "p2wpkh", Some(ScriptType::P2wpkh))),
+        [ScriptExpression::Taproot] => Some(("bip86", "p2tr", None)),
+        [ScriptExpression::ScriptHash, multi] if is_multisig(multi) => Some(("bip45", "p2sh", None)),
+        [ScriptExpression::ScriptHash, ScriptExpression::WitnessScriptHash, multi] if is_multisig(multi) => {
+            Some(("bip48_1", "p2sh-p2wsh", Some(ScriptType::P2shP2wsh)))
+        }
+        [ScriptExpression::WitnessScriptHash, multi] if is_multisig(multi) => {
+            Some(("bip48_2", "p2wsh", Some(ScriptType::P2wsh)))
+        }
+        _ => None,
+    }
+}
+
+impl IntoIterator for CryptoAccount {
+    type Item = CryptoOutput;
+    type IntoIter = std::vec::IntoIter<CryptoOutput>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.output_descriptors.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a CryptoAccount {
+    type Item = &'a CryptoOutput;
+    type IntoIter = std::slice::Iter<'a, CryptoOutput>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.output_descriptors.iter()
+    }
 }
 
 impl To for CryptoAccount {
@@ -55,23 +200,28 @@ impl To for CryptoAccount {
     }
     fn to_bytes(&self) -> Vec<u8> {
         let value = self.to_cbor();
-        to_vec(&value).unwrap()
+        encode(&value).unwrap()
     }
 }
 
 impl From<CryptoAccount> for CryptoAccount {
     fn from_cbor(cbor: Value) -> Result<CryptoAccount, String> {
         let value = CborValue::new(cbor);
-        let map = value.get_map()?;
+        let map = value.get_map().map_err(log_decode_error::<CryptoAccount>)?;
         let master_fingerprint =
             map.get_by_integer(MASTER_FINGERPRINT).map(|v| v.get_integer())
-                .transpose()?
+                .transpose()
+                .map_err(log_field_error::<CryptoAccount>(MASTER_FINGERPRINT))?
                 .map(|v| u32::to_be_bytes(v as u32))
-                .ok_or("master fingerprint is required for crypto-account".to_string())?;
+                .ok_or("master fingerprint is required for crypto-account".to_string())
+                .map_err(log_field_error::<CryptoAccount>(MASTER_FINGERPRINT))?;
         let outputs = map.get_by_integer(OUTPUT_DESCRIPTORS).map(|v| v.get_array())
-            .transpose()?
-            .ok_or("output descriptors are required for crypto-account".to_string())?
-            .iter().map(|v| CryptoOutput::from_cbor(v.get_value().clone())).collect::<Result<Vec<CryptoOutput>, String>>()?;
+            .transpose()
+            .map_err(log_field_error::<CryptoAccount>(OUTPUT_DESCRIPTORS))?
+            .ok_or("output descriptors are required for crypto-account".to_string())
+            .map_err(log_field_error::<CryptoAccount>(OUTPUT_DESCRIPTORS))?
+            .iter().map(|v| CryptoOutput::from_cbor(v.get_value().clone())).collect::<Result<Vec<CryptoOutput>, String>>()
+            .map_err(log_field_error::<CryptoAccount>(OUTPUT_DESCRIPTORS))?;
 
         Ok(CryptoAccount {
             master_fingerprint,
@@ -80,10 +230,144 @@ impl From<CryptoAccount> for CryptoAccount {
     }
 
     fn from_bytes(bytes: Vec<u8>) -> Result<CryptoAccount, String> {
-        let value: Value = match from_slice(bytes.as_slice()) {
-            Ok(x) => x,
-            Err(e) => return Err(e.to_string()),
-        };
+        let value = decode(bytes.as_slice())?;
         CryptoAccount::from_cbor(value)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto_account::CryptoAccount;
+    use crate::crypto_hd_key::{test_account_key, CryptoHDKey};
+    use crate::crypto_key_path::CryptoKeyPath;
+    use crate::crypto_output::CryptoOutput;
+    use crate::script_expression::ScriptExpression;
+    use crate::traits::{From, To};
+    use hex::FromHex;
+
+    #[test]
+    fn test_to_writer() {
+        let hd_key = CryptoHDKey::from_bytes(Vec::from_hex("A5035821026FE2355745BB2DB3630BBC80EF5D58951C963C841F54170BA6E5C12BE7FC12A6045820CED155C72456255881793514EDC5BD9447E7F74ABB88C6D6B6480FD016EE8C8505D90131A1020106D90130A1018A182CF501F501F500F401F4081AE9181CF3").unwrap()).unwrap();
+        let outputs = (0..50)
+            .map(|account| {
+                let origin = CryptoKeyPath::from_path(format!("m/84'/0'/{}'", account), Some([0x73, 0xc5, 0xda, 0x0a])).unwrap();
+                let key = CryptoHDKey::new_mainnet_extended(hd_key.get_key(), hd_key.get_chain_code().unwrap(), Some(origin));
+                CryptoOutput::new_with_script_expressions(vec![ScriptExpression::WitnessPublicKeyHash], key)
+            })
+            .collect();
+        let account = CryptoAccount::new([0x73, 0xc5, 0xda, 0x0a], outputs);
+        let mut streamed = vec![];
+        account.to_writer(&mut streamed).unwrap();
+        assert_eq!(account.to_bytes(), streamed);
+    }
+
+    #[test]
+    fn test_to_multi_accounts() {
+        let account = CryptoAccount::new(
+            [0x73, 0xc5, 0xda, 0x0a],
+            vec![
+                CryptoOutput::from(test_account_key("m/84'/0'/0'")),
+                CryptoOutput::from(test_account_key("m/84'/0'/1'")),
+                CryptoOutput::new_with_script_expressions(
+                    vec![ScriptExpression::WitnessScriptHash, ScriptExpression::SortedMultiSig],
+                    test_account_key("m/48'/0'/0'/2'"),
+                ),
+            ],
+        );
+        let multi_accounts = account.to_multi_accounts(Some("keystone".to_string()));
+        assert_eq!([0x73, 0xc5, 0xda, 0x0a], multi_accounts.get_master_fingerprint());
+        assert_eq!(Some("keystone".to_string()), multi_accounts.get_device());
+        let keys = multi_accounts.get_keys();
+        assert_eq!(2, keys.len());
+        assert_eq!(Some("84'/0'/0'".to_string()), keys[0].get_origin().unwrap().get_path());
+        assert_eq!(Some("84'/0'/1'".to_string()), keys[1].get_origin().unwrap().get_path());
+    }
+
+    #[test]
+    fn test_derivation_paths() {
+        let account = CryptoAccount::new(
+            [0x73, 0xc5, 0xda, 0x0a],
+            vec![
+                CryptoOutput::from(test_account_key("m/44'/0'/0'")),
+                CryptoOutput::from(test_account_key("m/49'/0'/0'")),
+                CryptoOutput::from(test_account_key("m/84'/0'/0'")),
+                CryptoOutput::from(CryptoHDKey::new_mainnet_extended(vec![0x02; 33], vec![0; 32], None)),
+            ],
+        );
+        let paths = account
+            .derivation_paths()
+            .iter()
+            .map(|path| path.get_path().unwrap())
+            .collect::<Vec<String>>();
+        assert_eq!(vec!["44'/0'/0'", "49'/0'/0'", "84'/0'/0'"], paths);
+    }
+
+    #[test]
+    fn test_to_descriptors_text() {
+        let output = CryptoOutput::from_bytes(Vec::from_hex("d90199d9012fa5035821023da5a8b563c3a6a5bb096e6a4cca9ddf581d82749967c97195905e25b30825190458206562e8f62c9df1d41dc572f30f5d66234f8247dfef114ac39db25c4bb97abc3b06d90130a201861856f500f500f5021a3442193e07d90130a1018400f480f4081af1053104").unwrap()).unwrap();
+        let account = CryptoAccount::new(
+            [0x34, 0x42, 0x19, 0x3e],
+            vec![
+                output.clone(),
+                CryptoOutput::new_with_script_expressions(vec![ScriptExpression::WitnessPublicKeyHash], output.get_crypto_key()),
+            ],
+        );
+        let text = account.to_descriptors_text();
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert_eq!(
+            vec![
+                "tr([3442193e/86'/0'/0']xpub6DRX1xNPHKaApgDnqaMNxJ8Lz35KCn3mRcW3LUep3JKhxWisRwaZJPn4BuZiaJ4kJ3cdqwbn4vZcsGiLGJJabZbqa65LGX2uhU9CtPWSgEn/0/*)#v86uyuy5",
+                "wpkh([3442193e/86'/0'/0']xpub6DRX1xNPHKaApgDnqaMNxJ8Lz35KCn3mRcW3LUep3JKhxWisRwaZJPn4BuZiaJ4kJ3cdqwbn4vZcsGiLGJJabZbqa65LGX2uhU9CtPWSgEn/0/*)#cy3pj20z",
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let account = CryptoAccount::new(
+            [0x73, 0xc5, 0xda, 0x0a],
+            vec![
+                CryptoOutput::from(test_account_key("m/44'/0'/0'")),
+                CryptoOutput::from(test_account_key("m/49'/0'/0'")),
+                CryptoOutput::from(test_account_key("m/84'/0'/0'")),
+            ],
+        );
+        let mut count = 0;
+        for output in &account {
+            assert!(output.get_crypto_key().get_origin().is_some());
+            count += 1;
+        }
+        assert_eq!(3, count);
+        assert_eq!(3, account.into_iter().filter(|output| !output.is_taproot()).count());
+    }
+
+    #[cfg(feature = "coldcard")]
+    #[test]
+    fn test_to_coldcard_json() {
+        let account = CryptoAccount::new(
+            [0x73, 0xc5, 0xda, 0x0a],
+            vec![
+                CryptoOutput::new_with_script_expressions(vec![ScriptExpression::PublicKeyHash], test_account_key("m/44'/0'/0'")),
+                CryptoOutput::new_with_script_expressions(
+                    vec![ScriptExpression::ScriptHash, ScriptExpression::WitnessPublicKeyHash],
+                    test_account_key("m/49'/0'/0'"),
+                ),
+                CryptoOutput::new_with_script_expressions(vec![ScriptExpression::WitnessPublicKeyHash], test_account_key("m/84'/0'/0'")),
+                CryptoOutput::new_with_script_expressions(vec![ScriptExpression::WitnessPublicKeyHash], test_account_key("m/84'/0'/1'")),
+                CryptoOutput::new_with_script_expressions(vec![ScriptExpression::Taproot], test_account_key("m/86'/0'/0'")),
+                CryptoOutput::new_with_script_expressions(vec![ScriptExpression::Combo], test_account_key("m/0'")),
+            ],
+        );
+        let xpub = "xpub6BemYiVNp19Zz8cJwY3X663dNHj5QRjN4dgPByiKNEFvKSBotr7D79u3ES1b3ci3hkUnrYAvPg7CHrsFbvxwCqdnJZ5y3pSjced8QvkSZmo";
+        let expected = [
+            "{\"chain\":\"BTC\",\"xfp\":\"73C5DA0A\",\"account\":0".to_string(),
+            format!("\"bip44\":{{\"name\":\"p2pkh\",\"xfp\":\"73C5DA0A\",\"deriv\":\"m/44'/0'/0'\",\"xpub\":\"{}\"}}", xpub),
+            format!("\"bip49\":{{\"name\":\"p2sh-p2wpkh\",\"xfp\":\"73C5DA0A\",\"deriv\":\"m/49'/0'/0'\",\"xpub\":\"{}\",\"_pub\":\"ypub6WV2rPAHxgh3qRoRmtq9JB98YFsXM3irykCbyNcCkEdoNY139WGmjDZBFdyB3XMy7Pbbc1mUrLTkB9UpKdNx15KPAtnPdjGDtNgmoTuXk2Y\"}}", xpub),
+            format!("\"bip84\":{{\"name\":\"p2wpkh\",\"xfp\":\"73C5DA0A\",\"deriv\":\"m/84'/0'/0'\",\"xpub\":\"{}\",\"_pub\":\"zpub6qKJA3qD7NEXgizYcFcmWGEdiE1yHfiMtripkmW68F1gRdpGQASLMHDKGqvm3S1tX2iQMVN3JzpJ4S6P3KnxoJzz3EUpDe5iA6kRC1SYrtB\"}}", xpub),
+            format!("\"bip86\":{{\"name\":\"p2tr\",\"xfp\":\"73C5DA0A\",\"deriv\":\"m/86'/0'/0'\",\"xpub\":\"{}\"}}}}", xpub),
+        ]
+        .join(",");
+        assert_eq!(expected, account.to_coldcard_json().unwrap());
+    }
+}
@@ -1,27 +1,150 @@
-use crate::cbor_value::{CborValue, CborValueMap};
+use crate::cbor_value::{log_decode_error, log_field_error, CborValue, CborValueMap};
 use crate::registry_types::{RegistryType, CRYPTO_COIN_INFO};
 use crate::traits::{From, RegistryItem, To};
-use serde_cbor::{from_slice, to_vec, Value};
+use crate::cbor::{decode, encode, Value};
 use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
 
 const COIN_TYPE: i128 = 1;
 const NETWORK: i128 = 2;
 
+// the SLIP-44 coin types with a name in this crate, any other index is kept in `Unknown`
 #[derive(Clone, Debug, PartialEq)]
 pub enum CoinType {
-    Bitcoin = 0,
+    Bitcoin,
+    Litecoin,
+    Dogecoin,
+    Dash,
+    Ethereum,
+    EthereumClassic,
+    Cosmos,
+    Monero,
+    Zcash,
+    Ripple,
+    BitcoinCash,
+    Stellar,
+    Eos,
+    Tron,
+    Algorand,
+    Polkadot,
+    Near,
+    Solana,
+    Tezos,
+    Cardano,
+    Unknown(u32),
+}
+
+// the most common SLIP-44 registrations: (coin type, index, name, ticker)
+const SLIP44_COINS: [(CoinType, u32, &str, &str); 20] = [
+    (CoinType::Bitcoin, 0, "Bitcoin", "BTC"),
+    (CoinType::Litecoin, 2, "Litecoin", "LTC"),
+    (CoinType::Dogecoin, 3, "Dogecoin", "DOGE"),
+    (CoinType::Dash, 5, "Dash", "DASH"),
+    (CoinType::Ethereum, 60, "Ethereum", "ETH"),
+    (CoinType::EthereumClassic, 61, "Ethereum Classic", "ETC"),
+    (CoinType::Cosmos, 118, "Cosmos", "ATOM"),
+    (CoinType::Monero, 128, "Monero", "XMR"),
+    (CoinType::Zcash, 133, "Zcash", "ZEC"),
+    (CoinType::Ripple, 144, "Ripple", "XRP"),
+    (CoinType::BitcoinCash, 145, "Bitcoin Cash", "BCH"),
+    (CoinType::Stellar, 148, "Stellar", "XLM"),
+    (CoinType::Eos, 194, "EOS", "EOS"),
+    (CoinType::Tron, 195, "Tron", "TRX"),
+    (CoinType::Algorand, 283, "Algorand", "ALGO"),
+    (CoinType::Polkadot, 354, "Polkadot", "DOT"),
+    (CoinType::Near, 397, "Near", "NEAR"),
+    (CoinType::Solana, 501, "Solana", "SOL"),
+    (CoinType::Tezos, 1729, "Tezos", "XTZ"),
+    (CoinType::Cardano, 1815, "Cardano", "ADA"),
+];
+
+impl CoinType {
+    pub fn from_u32(value: u32) -> CoinType {
+        SLIP44_COINS
+            .iter()
+            .find(|(_, index, _, _)| *index == value)
+            .map_or(CoinType::Unknown(value), |(coin_type, _, _, _)| coin_type.clone())
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        match self {
+            CoinType::Unknown(index) => *index,
+            coin_type => SLIP44_COINS
+                .iter()
+                .find(|(x, _, _, _)| x == coin_type)
+                .map(|(_, index, _, _)| *index)
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn get_ticker(&self) -> Option<&'static str> {
+        SLIP44_COINS
+            .iter()
+            .find(|(x, _, _, _)| x == self)
+            .map(|(_, _, _, ticker)| *ticker)
+    }
+}
+
+impl fmt::Display for CoinType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match SLIP44_COINS.iter().find(|(x, _, _, _)| x == self) {
+            Some((_, _, name, _)) => write!(f, "{}", name),
+            None => write!(f, "slip44:{}", self.to_u32()),
+        }
+    }
+}
+
+// accepts the display name or ticker of a listed coin ignoring case, or slip44:<index> of any coin
+impl FromStr for CoinType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(index) = s.strip_prefix("slip44:") {
+            return index
+                .parse::<u32>()
+                .map(CoinType::from_u32)
+                .map_err(|_| format!("invalid slip44 index: {}", index));
+        }
+        SLIP44_COINS
+            .iter()
+            .find(|(_, _, name, ticker)| name.eq_ignore_ascii_case(s) || ticker.eq_ignore_ascii_case(s))
+            .map(|(coin_type, _, _, _)| coin_type.clone())
+            .ok_or(format!("unknown coin type: {}", s))
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Network {
-    MainNet = 0,
-    TestNet = 1,
+    MainNet,
+    TestNet,
+    // values other than 0 and 1 are not defined by the spec, they are kept so they survive a round trip
+    Unknown(i128),
 }
 
+impl Network {
+    pub fn from_i128(value: i128) -> Network {
+        match value {
+            0 => Network::MainNet,
+            1 => Network::TestNet,
+            x => Network::Unknown(x),
+        }
+    }
+
+    pub fn to_i128(&self) -> i128 {
+        match self {
+            Network::MainNet => 0,
+            Network::TestNet => 1,
+            Network::Unknown(x) => *x,
+        }
+    }
+}
+
+// the fields hold the encoded integers so values this crate doesn't know are written back unchanged
 #[derive(Clone, Debug, Default)]
 pub struct CryptoCoinInfo {
-    coin_type: Option<CoinType>,
-    network: Option<Network>,
+    coin_type: Option<u32>,
+    network: Option<i128>,
 }
 
 impl CryptoCoinInfo {
@@ -30,21 +153,41 @@ impl CryptoCoinInfo {
     }
 
     pub fn set_coin_type(&mut self, coin_type: CoinType) {
-        self.coin_type = Some(coin_type)
+        self.coin_type = Some(coin_type.to_u32())
     }
 
     pub fn set_network(&mut self, network: Network) {
-        self.network = Some(network)
+        self.network = Some(network.to_i128())
     }
 
     pub fn new(coin_type: Option<CoinType>, network: Option<Network>) -> CryptoCoinInfo {
+        CryptoCoinInfo {
+            coin_type: coin_type.map(|x| x.to_u32()),
+            network: network.map(|x| x.to_i128()),
+        }
+    }
+
+    // the encoded integers as they are, e.g. from a decoder that doesn't build the enums
+    pub fn new_raw(coin_type: Option<u32>, network: Option<i128>) -> CryptoCoinInfo {
         CryptoCoinInfo { coin_type, network }
     }
     pub fn get_coin_type(&self) -> CoinType {
-        self.coin_type.clone().unwrap_or(CoinType::Bitcoin)
+        self.coin_type.map_or(CoinType::Bitcoin, CoinType::from_u32)
     }
     pub fn get_network(&self) -> Network {
-        self.network.clone().unwrap_or(Network::MainNet)
+        self.network.map_or(Network::MainNet, Network::from_i128)
+    }
+    // the encoded integer, None when the coin type was omitted rather than defaulting to Bitcoin
+    pub fn get_coin_type_raw(&self) -> Option<u32> {
+        self.coin_type
+    }
+    // the encoded integer, None when the network was omitted rather than defaulting to mainnet
+    pub fn get_network_raw(&self) -> Option<i128> {
+        self.network
+    }
+    // the coin name, slip44:<index> for coin types without a name
+    pub fn get_coin_type_name(&self) -> String {
+        self.get_coin_type().to_string()
     }
 }
 
@@ -52,17 +195,15 @@ impl To for CryptoCoinInfo {
     fn to_cbor(&self) -> Value {
         let mut map = BTreeMap::<Value, Value>::new();
         self.coin_type
-            .clone()
             .and_then(|x| map.insert(Value::Integer(COIN_TYPE), Value::Integer(x as i128)));
         self.network
-            .clone()
-            .and_then(|x| map.insert(Value::Integer(NETWORK), Value::Integer(x as i128)));
+            .and_then(|x| map.insert(Value::Integer(NETWORK), Value::Integer(x)));
         Value::Map(map)
     }
 
     fn to_bytes(&self) -> Vec<u8> {
         let value = self.to_cbor();
-        to_vec(&value).unwrap()
+        encode(&value).unwrap()
     }
 }
 
@@ -75,32 +216,124 @@ impl RegistryItem for CryptoCoinInfo {
 impl From<CryptoCoinInfo> for CryptoCoinInfo {
     fn from_cbor(cbor: Value) -> Result<CryptoCoinInfo, String> {
         let value = CborValue::new(cbor);
-        let map: CborValueMap = value.get_map()?;
+        let map: CborValueMap = value.get_map().map_err(log_decode_error::<CryptoCoinInfo>)?;
         let coin_type = map
             .get_by_integer(COIN_TYPE)
             .map(|v| v.get_integer())
-            .transpose()?
-            .map(|v| match v {
-                0 => CoinType::Bitcoin,
-                _ => CoinType::Bitcoin,
-            });
+            .transpose()
+            .map_err(log_field_error::<CryptoCoinInfo>(COIN_TYPE))?
+            .map(|v| u32::try_from(v).map_err(|_| format!("coin type out of range: {}", v)))
+            .transpose()
+            .map_err(log_field_error::<CryptoCoinInfo>(COIN_TYPE))?;
         let network = map
             .get_by_integer(NETWORK)
             .map(|v| v.get_integer())
-            .transpose()?
-            .map(|v| match v {
-                0 => Network::MainNet,
-                1 => Network::TestNet,
-                _ => Network::MainNet,
-            });
+            .transpose()
+            .map_err(log_field_error::<CryptoCoinInfo>(NETWORK))?;
         Ok(CryptoCoinInfo { coin_type, network })
     }
 
     fn from_bytes(bytes: Vec<u8>) -> Result<CryptoCoinInfo, String> {
-        let value: Value = match from_slice(bytes.as_slice()) {
-            Ok(x) => x,
-            Err(e) => return Err(e.to_string()),
-        };
+        let value = decode(bytes.as_slice())?;
         CryptoCoinInfo::from_cbor(value)
     }
 }
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for CryptoCoinInfo {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(CryptoCoinInfo {
+            coin_type: u.arbitrary::<Option<u32>>()?,
+            network: u.arbitrary::<Option<u32>>()?.map(|x| x as i128),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto_coin_info::{CoinType, CryptoCoinInfo, Network};
+    use std::str::FromStr;
+    use crate::traits::{From, To};
+    use hex::FromHex;
+
+    #[test]
+    fn test_decode_network() {
+        let main_net = CryptoCoinInfo::from_bytes(Vec::from_hex("a10200").unwrap()).unwrap();
+        assert_eq!(Network::MainNet, main_net.get_network());
+        let test_net = CryptoCoinInfo::from_bytes(Vec::from_hex("a10201").unwrap()).unwrap();
+        assert_eq!(Network::TestNet, test_net.get_network());
+
+        let unknown = CryptoCoinInfo::from_bytes(Vec::from_hex("a10202").unwrap()).unwrap();
+        assert_eq!(Network::Unknown(2), unknown.get_network());
+        assert_eq!("a10202", hex::encode(unknown.to_bytes()));
+    }
+
+    #[test]
+    fn test_coin_type_display() {
+        assert_eq!("Bitcoin", CoinType::Bitcoin.to_string());
+        assert_eq!("Ethereum", CoinType::from_u32(60).to_string());
+        assert_eq!(CoinType::Unknown(9999), CoinType::from_u32(9999));
+        assert_eq!("slip44:9999", CoinType::from_u32(9999).to_string());
+        assert_eq!(60, CoinType::Ethereum.to_u32());
+        assert_eq!(9999, CoinType::Unknown(9999).to_u32());
+        assert_eq!(Some("ETH"), CoinType::Ethereum.get_ticker());
+
+        assert_eq!(CoinType::Bitcoin, CoinType::from_str("BTC").unwrap());
+        assert_eq!(CoinType::Ethereum, CoinType::from_str("ethereum").unwrap());
+        assert_eq!(CoinType::Unknown(9999), CoinType::from_str("slip44:9999").unwrap());
+        assert_eq!(CoinType::Unknown(9999), CoinType::from_str(&CoinType::Unknown(9999).to_string()).unwrap());
+        assert!(CoinType::from_str("slip44:-1").is_err());
+        assert_eq!(CoinType::Litecoin, CoinType::from_str("slip44:2").unwrap());
+        assert!(CoinType::from_str("not-a-coin").is_err());
+    }
+
+    #[test]
+    fn test_get_network_raw() {
+        let coin_info = CryptoCoinInfo::from_bytes(Vec::from_hex("a201183c0205").unwrap()).unwrap();
+        assert_eq!(Some(5), coin_info.get_network_raw());
+        assert_eq!(Network::Unknown(5), coin_info.get_network());
+        let coin_info = CryptoCoinInfo::from_bytes(Vec::from_hex("a10201").unwrap()).unwrap();
+        assert_eq!(Some(1), coin_info.get_network_raw());
+        let coin_info = CryptoCoinInfo::from_bytes(Vec::from_hex("a0").unwrap()).unwrap();
+        assert_eq!(None, coin_info.get_network_raw());
+        assert_eq!(Network::MainNet, coin_info.get_network());
+    }
+
+    #[test]
+    fn test_decode_empty_map() {
+        // producers may omit both fields, the spec defaults are Bitcoin mainnet
+        let coin_info = CryptoCoinInfo::from_bytes(Vec::from_hex("a0").unwrap()).unwrap();
+        assert_eq!(CoinType::Bitcoin, coin_info.get_coin_type());
+        assert_eq!(Network::MainNet, coin_info.get_network());
+        assert_eq!("a0", hex::encode(coin_info.to_bytes()));
+    }
+
+    #[test]
+    fn test_unknown_coin_type() {
+        let coin_info = CryptoCoinInfo::from_bytes(Vec::from_hex("a10119270f").unwrap()).unwrap();
+        assert_eq!(CoinType::Unknown(9999), coin_info.get_coin_type());
+        assert_eq!(Some(9999), coin_info.get_coin_type_raw());
+        assert_eq!("slip44:9999", coin_info.get_coin_type_name());
+        assert_eq!("a10119270f", hex::encode(coin_info.to_bytes()));
+        // 2^32 must not wrap around to Bitcoin
+        assert_eq!(
+            "coin type out of range: 4294967296",
+            CryptoCoinInfo::from_bytes(Vec::from_hex("a1011b0000000100000000").unwrap()).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_encode_unknown_variants() {
+        // the values carried by the variants are what gets written
+        let coin_info = CryptoCoinInfo::new(Some(CoinType::Unknown(9999)), Some(Network::Unknown(2)));
+        assert_eq!("a20119270f0202", hex::encode(coin_info.to_bytes()));
+        let mut coin_info = CryptoCoinInfo::default();
+        coin_info.set_coin_type(CoinType::Litecoin);
+        coin_info.set_network(Network::TestNet);
+        assert_eq!("a201020201", hex::encode(coin_info.to_bytes()));
+        coin_info.set_coin_type(CoinType::Unknown(9999));
+        let decoded = CryptoCoinInfo::from_bytes(coin_info.to_bytes()).unwrap();
+        assert_eq!(CoinType::Unknown(9999), decoded.get_coin_type());
+        assert_eq!(Network::TestNet, decoded.get_network());
+    }
+}
@@ -1,7 +1,7 @@
-use crate::cbor_value::CborValue;
+use crate::cbor_value::{log_decode_error, log_field_error, CborValue};
 use crate::registry_types::{RegistryType, CRYPTO_ECKEY};
 use crate::traits::{From, RegistryItem, To};
-use serde_cbor::{from_slice, to_vec, Value};
+use crate::cbor::{decode, encode, Value};
 use std::collections::BTreeMap;
 use crate::types::Bytes;
 
@@ -79,25 +79,28 @@ impl To for CryptoECKey {
 
     fn to_bytes(&self) -> Vec<u8> {
         let value = self.to_cbor();
-        to_vec(&value).unwrap()
+        encode(&value).unwrap()
     }
 }
 
 impl From<CryptoECKey> for CryptoECKey {
     fn from_cbor(cbor: Value) -> Result<CryptoECKey, String> {
         let value = CborValue::new(cbor);
-        let map = value.get_map()?;
+        let map = value.get_map().map_err(log_decode_error::<CryptoECKey>)?;
         let curve = map
             .get_by_integer(CURVE)
             .map(|v| v.get_integer())
-            .transpose()?;
+            .transpose()
+            .map_err(log_field_error::<CryptoECKey>(CURVE))?;
         let is_private_key = map
             .get_by_integer(PRIVATE)
             .map(|v| v.get_bool())
-            .transpose()?;
+            .transpose()
+            .map_err(log_field_error::<CryptoECKey>(PRIVATE))?;
         let data = map
             .get_by_integer(DATA)
-            .map_or(Ok(vec![]), |v| v.get_bytes())?;
+            .map_or(Ok(vec![]), |v| v.get_bytes())
+            .map_err(log_field_error::<CryptoECKey>(DATA))?;
         Ok(CryptoECKey {
             curve,
             is_private_key,
@@ -106,10 +109,7 @@ impl From<CryptoECKey> for CryptoECKey {
     }
 
     fn from_bytes(bytes: Vec<u8>) -> Result<CryptoECKey, String> {
-        let value: Value = match from_slice(bytes.as_slice()) {
-            Ok(x) => x,
-            Err(e) => return Err(e.to_string()),
-        };
+        let value = decode(bytes.as_slice())?;
         CryptoECKey::from_cbor(value)
     }
 }
@@ -1,10 +1,18 @@
-use crate::cbor_value::CborValue;
-use crate::crypto_coin_info::CryptoCoinInfo;
+use crate::cbor_value::{log_decode_error, log_field_error, CborValue};
+use crate::crypto_coin_info::{CoinType, CryptoCoinInfo, Network};
+#[cfg(feature = "secp256k1")]
+use crate::crypto::{address, bip32, secp};
+#[cfg(feature = "secp256k1")]
+use crate::crypto::address::AddressParams;
+#[cfg(feature = "secp256k1")]
+use secp256k1::PublicKey;
+use crate::crypto_key_path::PathComponent;
 use crate::crypto_key_path::CryptoKeyPath;
+use crate::ext_key_version::{get_version, parse_version, ScriptType};
 use crate::registry_types::{RegistryType, CRYPTO_COIN_INFO, CRYPTO_HDKEY, CRYPTO_KEYPATH};
 use crate::traits::{From, RegistryItem, To};
 use crate::types::{Bytes, Fingerprint};
-use serde_cbor::{from_slice, to_vec, Value};
+use crate::cbor::{check_depth, decode, encode, Value, MAX_DEPTH};
 use std::collections::BTreeMap;
 
 const IS_MASTER: i128 = 1;
@@ -18,7 +26,7 @@ const PARENT_FINGERPRINT: i128 = 8;
 const NAME: i128 = 9;
 const NOTE: i128 = 10;
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
 pub struct CryptoHDKey {
     is_master: Option<bool>,
     is_private_key: Option<bool>,
@@ -26,12 +34,32 @@ pub struct CryptoHDKey {
     chain_code: Option<Bytes>,
     use_info: Option<CryptoCoinInfo>,
     origin: Option<CryptoKeyPath>,
-    children: Option<CryptoKeyPath>,
+    // producers may list several children paths (e.g. receive and change), most only send one
+    children: Vec<CryptoKeyPath>,
     parent_fingerprint: Option<Fingerprint>,
     name: Option<String>,
     note: Option<String>,
 }
 
+// an empty key, kept for struct update syntax and FFI callers. use the constructors or `try_new` instead
+#[doc(hidden)]
+impl Default for CryptoHDKey {
+    fn default() -> Self {
+        CryptoHDKey {
+            is_master: None,
+            is_private_key: None,
+            key: vec![],
+            chain_code: None,
+            use_info: None,
+            origin: None,
+            children: vec![],
+            parent_fingerprint: None,
+            name: None,
+            note: None,
+        }
+    }
+}
+
 impl CryptoHDKey {
     pub fn new_master_key(key: Bytes, chain_code: Bytes) -> CryptoHDKey {
         CryptoHDKey {
@@ -60,13 +88,88 @@ impl CryptoHDKey {
             chain_code,
             use_info,
             origin,
-            children,
+            children: children.into_iter().collect(),
             parent_fingerprint,
             name,
             note,
         }
     }
 
+    // same as `new_extended_key` but checks the key data and chain code lengths up front
+    pub fn try_new(
+        is_private_key: Option<bool>,
+        key: Bytes,
+        chain_code: Option<Bytes>,
+        use_info: Option<CryptoCoinInfo>,
+        origin: Option<CryptoKeyPath>,
+        children: Option<CryptoKeyPath>,
+        parent_fingerprint: Option<Fingerprint>,
+        name: Option<String>,
+        note: Option<String>,
+    ) -> Result<CryptoHDKey, String> {
+        if key.len() != 33 {
+            return Err(format!("invalid key length {}, expected 33 bytes", key.len()));
+        }
+        match (is_private_key.unwrap_or(false), key[0]) {
+            (true, 0x00) | (false, 0x02) | (false, 0x03) => {}
+            (true, x) => return Err(format!("invalid private key prefix {:#04x}, expected 0x00", x)),
+            (false, x) => {
+                return Err(format!("invalid public key prefix {:#04x}, expected 0x02 or 0x03", x))
+            }
+        }
+        if let Some(x) = &chain_code {
+            if x.len() != 32 {
+                return Err(format!("invalid chain code length {}, expected 32 bytes", x.len()));
+            }
+        }
+        Ok(CryptoHDKey::new_extended_key(
+            is_private_key,
+            key,
+            chain_code,
+            use_info,
+            origin,
+            children,
+            parent_fingerprint,
+            name,
+            note,
+        ))
+    }
+
+    pub fn new_mainnet_extended(
+        key: Bytes,
+        chain_code: Bytes,
+        origin: Option<CryptoKeyPath>,
+    ) -> CryptoHDKey {
+        CryptoHDKey::new_bitcoin_extended(key, chain_code, origin, Network::MainNet)
+    }
+
+    pub fn new_testnet_extended(
+        key: Bytes,
+        chain_code: Bytes,
+        origin: Option<CryptoKeyPath>,
+    ) -> CryptoHDKey {
+        CryptoHDKey::new_bitcoin_extended(key, chain_code, origin, Network::TestNet)
+    }
+
+    fn new_bitcoin_extended(
+        key: Bytes,
+        chain_code: Bytes,
+        origin: Option<CryptoKeyPath>,
+        network: Network,
+    ) -> CryptoHDKey {
+        CryptoHDKey::new_extended_key(
+            None,
+            key,
+            Some(chain_code),
+            Some(CryptoCoinInfo::new(Some(CoinType::Bitcoin), Some(network))),
+            origin,
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
     pub fn is_master(&self) -> bool {
         self.is_master.clone().unwrap_or(false)
     }
@@ -82,12 +185,68 @@ impl CryptoHDKey {
     pub fn get_use_info(&self) -> Option<CryptoCoinInfo> {
         self.use_info.clone()
     }
+    // crypto-hdkey carries no version bytes, the network of `use_info` is the only source. keys
+    // without use-info default to mainnet
+    pub fn is_testnet(&self) -> bool {
+        self.use_info
+            .as_ref()
+            .map_or(false, |use_info| use_info.get_network() == Network::TestNet)
+    }
     pub fn get_origin(&self) -> Option<CryptoKeyPath> {
         self.origin.clone()
     }
+    // the depth and child number of `get_bip32_key` are derived from the origin, so they follow it
+    pub fn set_origin(&mut self, origin: CryptoKeyPath) {
+        self.origin = Some(origin);
+    }
+    pub fn with_origin(mut self, origin: CryptoKeyPath) -> Self {
+        self.set_origin(origin);
+        self
+    }
     pub fn get_children(&self) -> Option<CryptoKeyPath> {
+        self.children.first().cloned()
+    }
+    // origin followed by the first children path, e.g. 84'/0'/0'/0/*. None when the key has neither
+    pub fn full_key_path(&self) -> Option<CryptoKeyPath> {
+        let children = self.get_children();
+        if self.origin.is_none() && children.is_none() {
+            return None;
+        }
+        let origin = self.origin.clone().unwrap_or(CryptoKeyPath::new(vec![], None, None));
+        let children = children.map_or(vec![], |children| children.get_components());
+        let mut components = origin.get_components();
+        components.extend(children.iter().cloned());
+        Some(CryptoKeyPath::new(
+            components,
+            origin.get_source_fingerprint(),
+            origin.get_depth().map(|depth| depth + children.len() as u32),
+        ))
+    }
+    // the first index of each children path, [0] for 0/*. a <0;1>/* key is carried as the two
+    // children paths 0/* and 1/* and gives [0, 1]
+    pub fn change_indexes(&self) -> Vec<u32> {
+        let mut indexes = vec![];
+        self.children
+            .iter()
+            .filter_map(|children| {
+                children
+                    .get_components()
+                    .first()
+                    .and_then(|component| component.get_index())
+            })
+            .for_each(|index| {
+                if !indexes.contains(&index) {
+                    indexes.push(index)
+                }
+            });
+        indexes
+    }
+    pub fn get_children_paths(&self) -> Vec<CryptoKeyPath> {
         self.children.clone()
     }
+    pub fn set_children_paths(&mut self, children: Vec<CryptoKeyPath>) {
+        self.children = children;
+    }
     pub fn get_parent_fingerprint(&self) -> Option<Fingerprint> {
         self.parent_fingerprint.clone()
     }
@@ -99,33 +258,109 @@ impl CryptoHDKey {
     }
 
     pub fn get_bip32_key(&self) -> String {
+        bs58::encode(self.extended_key_data()).with_check().into_string()
+    }
+
+    // like `get_bip32_key` but with the SLIP-132 prefix of the script type, e.g. zpub for P2wpkh
+    pub fn get_slip132_key(&self, script_type: ScriptType) -> Result<String, String> {
+        let (coin_type, network) = self
+            .use_info
+            .as_ref()
+            .map_or((CoinType::Bitcoin, Network::MainNet), |x| (x.get_coin_type(), x.get_network()));
+        let version = get_version(&coin_type, &network, script_type, self.is_private_key()).ok_or(format!(
+            "no extended key version for {:?} {:?} {:?}",
+            coin_type, network, script_type
+        ))?;
+        self.check_chain_code()?;
+        let mut data = self.extended_key_data();
+        data[0..4].copy_from_slice(&version);
+        Ok(bs58::encode(data).with_check().into_string())
+    }
+
+    // accepts plain BIP32 and SLIP-132 keys. crypto-hdkey has no field for the script type the
+    // prefix commits to, so it is returned next to the key
+    pub fn from_extended_key(extended_key: &str) -> Result<(CryptoHDKey, ScriptType), String> {
+        let data = bs58::decode(extended_key)
+            .with_check(None)
+            .into_vec()
+            .map_err(|e| format!("invalid extended key: {}", e))?;
+        if data.len() != 78 {
+            return Err(format!("invalid extended key length {}, expected 78 bytes", data.len()));
+        }
+        let (coin_type, network, script_type, is_private_key) =
+            parse_version([data[0], data[1], data[2], data[3]])
+                .ok_or(format!("unknown extended key version {}", hex::encode(&data[0..4])))?;
+        let depth = data[4];
+        let index = u32::from_be_bytes([data[9], data[10], data[11], data[12]]);
+        let (origin, parent_fingerprint) = match depth {
+            0 => (None, None),
+            _ => (
+                Some(CryptoKeyPath::new(
+                    vec![PathComponent::new(Some(index & 0x7fffffff), index & 0x80000000 != 0)?],
+                    None,
+                    Some(depth as u32),
+                )),
+                Some([data[5], data[6], data[7], data[8]]),
+            ),
+        };
+        let hd_key = CryptoHDKey::new_extended_key(
+            Some(is_private_key),
+            data[45..78].to_vec(),
+            Some(data[13..45].to_vec()),
+            Some(CryptoCoinInfo::new(Some(coin_type), Some(network))),
+            origin,
+            None,
+            parent_fingerprint,
+            None,
+            None,
+        );
+        Ok((hd_key, script_type))
+    }
+
+    // version || depth || parent fingerprint || child number || chain code || key, as encoded by get_bip32_key
+    pub fn to_extended_key_bytes(&self) -> Result<[u8; 78], String> {
+        self.check_chain_code()?;
+        let data = self.extended_key_data();
+        let length = data.len();
+        data.try_into()
+            .map_err(|_| format!("invalid extended key length {}, expected 78 bytes", length))
+    }
+
+    // depth and child number of the extended key serialization
+    fn extended_key_position(&self) -> (u8, u32) {
+        match (self.is_master(), &self.origin) {
+            (false, Some(origin)) => (
+                origin.get_depth().unwrap_or(origin.len() as u32) as u8,
+                origin
+                    .get_components()
+                    .last()
+                    .and_then(|component| component.get_canonical_index())
+                    .unwrap_or(0),
+            ),
+            _ => (0, 0),
+        }
+    }
+
+    fn check_chain_code(&self) -> Result<(), String> {
+        match self.chain_code.as_ref().map(|chain_code| chain_code.len()) {
+            None | Some(32) => Ok(()),
+            Some(length) => Err(format!("invalid chain code length {}, expected 32 bytes", length)),
+        }
+    }
+
+    fn extended_key_data(&self) -> Vec<u8> {
         let mut version: Bytes;
-        let mut depth: u8 = 0;
-        let mut index: u32 = 0;
+        let (depth, index) = self.extended_key_position();
         let parent_fingerprint: Fingerprint = self.parent_fingerprint.unwrap_or([0, 0, 0, 0]);
+        // the chain code field is fixed at 32 bytes, a malformed one is padded or cut so the key
+        // stays at its offset. the Result returning callers reject it through check_chain_code
         let mut chain_code = self.get_chain_code().unwrap_or(vec![0; 32]);
+        chain_code.resize(32, 0);
         let mut key = self.get_key();
         if self.is_master() {
             version = vec![0x04, 0x88, 0xAD, 0xE4];
-            depth = 0;
-            index = 0;
         } else {
-            match self.get_origin() {
-                Some(x) => {
-                    depth = x.get_components().len() as u8;
-                    index = x
-                        .get_components()
-                        .last()
-                        .unwrap()
-                        .get_canonical_index()
-                        .unwrap_or(0);
-                }
-                None => {}
-            };
-            version = match self.is_private_key() {
-                true => vec![0x04, 0x88, 0xAD, 0xE4],
-                false => vec![0x04, 0x88, 0xB2, 0x1E],
-            }
+            version = extended_key_version(self.use_info.as_ref(), self.is_private_key()).to_vec();
         }
         let mut output = vec![];
         output.append(version.as_mut()); // 4
@@ -134,7 +369,7 @@ impl CryptoHDKey {
         output.append(index.to_be_bytes().to_vec().as_mut()); // 4
         output.append(chain_code.as_mut()); //32
         output.append(key.as_mut()); //33
-        bs58::encode(output).with_check().into_string()
+        output
     }
 
     pub fn get_account_index(&self, level: u32) -> Option<u32> {
@@ -152,6 +387,189 @@ impl CryptoHDKey {
     pub fn get_depth(&self) -> Option<u32> {
         self.origin.clone().map_or(None, |v| v.get_depth())
     }
+
+    // purpose'/coin_type'/account', the depth an account xpub is exported at
+    pub fn is_account_level(&self) -> bool {
+        match (self.is_master(), &self.origin) {
+            (false, Some(origin)) => {
+                origin.get_depth().unwrap_or(origin.len() as u32) == 3
+                    && origin
+                        .get_components()
+                        .last()
+                        .map_or(false, |component| component.is_hardened())
+            }
+            _ => false,
+        }
+    }
+
+    // flags metadata that cannot describe a real derivation, usually a bug in the producer
+    pub fn consistency_warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        let origin = match &self.origin {
+            Some(x) => x,
+            None => return warnings,
+        };
+        let depth = origin.get_depth().unwrap_or(origin.len() as u32);
+        match (self.parent_fingerprint, origin.get_source_fingerprint(), depth) {
+            (Some(parent), _, 0) if parent != [0, 0, 0, 0] => warnings.push(format!(
+                "parent fingerprint {} is set but the origin depth is 0",
+                hex::encode(parent)
+            )),
+            (Some(parent), Some(source), 1) if parent != source => warnings.push(format!(
+                "parent fingerprint {} does not match source fingerprint {} at depth 1",
+                hex::encode(parent),
+                hex::encode(source)
+            )),
+            _ => {}
+        }
+        warnings
+    }
+
+    // true when both keys serialize to the same extended key. name and note are ignored, use info
+    // only matters through the version prefix, e.g. Ltub for a Litecoin key
+    pub fn same_key(&self, other: &Self) -> bool {
+        self.extended_key_data() == other.extended_key_data()
+    }
+
+    // computed for master keys, extended keys report the source fingerprint of their origin
+    #[cfg(feature = "secp256k1")]
+    pub fn master_fingerprint(&self) -> Result<Fingerprint, String> {
+        if self.is_master() {
+            return Ok(bip32::fingerprint(&secp::public_key_from_private(&self.key)?));
+        }
+        self.origin
+            .as_ref()
+            .and_then(|origin| origin.get_source_fingerprint())
+            .ok_or("master fingerprint is unknown, origin has no source fingerprint".to_string())
+    }
+
+    #[cfg(feature = "secp256k1")]
+    pub fn neutered(&self) -> Result<CryptoHDKey, String> {
+        if !self.is_master() && !self.is_private_key() {
+            return Ok(self.clone());
+        }
+        Ok(CryptoHDKey {
+            is_master: Some(false),
+            is_private_key: Some(false),
+            key: secp::public_key_from_private(&self.key)?.to_vec(),
+            ..self.clone()
+        })
+    }
+
+    // derives <change>/<index> children of a Bitcoin account key and renders them with the script type implied
+    // by the origin purpose and the network of use_info, other coins and purposes are an error
+    #[cfg(feature = "secp256k1")]
+    pub fn addresses(&self, change: bool, start: u32, count: u32) -> Result<Vec<String>, String> {
+        let account_key = self.neutered()?;
+        let chain_code = account_key
+            .get_chain_code()
+            .ok_or("chain code is required for key derivation".to_string())?;
+        let (coin_type, network) = account_key
+            .get_use_info()
+            .map_or((CoinType::Bitcoin, Network::MainNet), |x| (x.get_coin_type(), x.get_network()));
+        let params = match (&coin_type, &network) {
+            (CoinType::Bitcoin, Network::MainNet) => &address::BITCOIN_MAINNET,
+            (CoinType::Bitcoin, Network::TestNet) => &address::BITCOIN_TESTNET,
+            _ => return Err(format!("unsupported coin for addresses: {} {:?}", coin_type, network)),
+        };
+        // only the single key purposes have an implied script, anything else is refused rather than guessed
+        let purpose = account_key
+            .get_origin()
+            .and_then(|origin| origin.get_components().first().cloned())
+            .filter(|component| component.is_hardened())
+            .and_then(|component| component.get_index());
+        let render: fn(&PublicKey, &AddressParams) -> Result<String, String> = match purpose {
+            Some(44) => |key, params| Ok(address::p2pkh(key, params)),
+            Some(49) => |key, params| Ok(address::p2sh_p2wpkh(key, params)),
+            Some(84) => address::p2wpkh,
+            Some(86) => address::p2tr,
+            _ => return Err(format!("no address script type for purpose {:?}", purpose)),
+        };
+        let (change_key, change_chain_code) = bip32::derive_public_child(
+            &secp::public_key(&account_key.get_key())?,
+            &chain_code,
+            change as u32,
+        )?;
+        (start..start.checked_add(count).ok_or("address range overflows".to_string())?)
+            .map(|index| {
+                let (key, _) = bip32::derive_public_child(&change_key, &change_chain_code, index)?;
+                render(&key, params)
+            })
+            .collect()
+    }
+
+    // derives m/purpose'/coin_type'/account' from a private master key and returns the public account key
+    #[cfg(feature = "secp256k1")]
+    pub fn account_xpub(
+        &self,
+        purpose: u32,
+        coin_type: u32,
+        account: u32,
+    ) -> Result<CryptoHDKey, String> {
+        if !self.is_master() {
+            return Err("account keys can only be derived from a master key".to_string());
+        }
+        let chain_code = self
+            .get_chain_code()
+            .ok_or("chain code is required for key derivation".to_string())?;
+        let master_key = secp::secret_key(&self.key)?;
+        let master_fingerprint = bip32::fingerprint(&secp::public_key_from_private(&self.key)?);
+        let components = vec![
+            PathComponent::new(Some(purpose), true)?,
+            PathComponent::new(Some(coin_type), true)?,
+            PathComponent::new(Some(account), true)?,
+        ];
+        let indexes = components
+            .iter()
+            .filter_map(|component| component.get_canonical_index())
+            .collect::<Vec<u32>>();
+        let (parent_key, parent_chain_code) =
+            bip32::derive_private_path(&master_key, &chain_code, &indexes[..2])?;
+        let (account_key, account_chain_code) =
+            bip32::derive_private_child(&parent_key, &parent_chain_code, indexes[2])?;
+        let parent_fingerprint =
+            bip32::fingerprint(&secp::public_key_from_private(&parent_key.secret_bytes())?);
+        let use_info = match coin_type {
+            0 => Some(CryptoCoinInfo::new(Some(CoinType::Bitcoin), Some(Network::MainNet))),
+            1 => Some(CryptoCoinInfo::new(Some(CoinType::Bitcoin), Some(Network::TestNet))),
+            _ => None,
+        };
+        Ok(CryptoHDKey::new_extended_key(
+            Some(false),
+            secp::public_key_from_private(&account_key.secret_bytes())?.to_vec(),
+            Some(account_chain_code),
+            use_info,
+            Some(CryptoKeyPath::new(components, Some(master_fingerprint), Some(3))),
+            None,
+            Some(parent_fingerprint),
+            None,
+            None,
+        ))
+    }
+}
+
+// Bitcoin keys are rendered as xpub/xprv on every network as existing consumers expect, Litecoin and
+// Dogecoin use their own prefixes
+fn extended_key_version(use_info: Option<&CryptoCoinInfo>, is_private_key: bool) -> [u8; 4] {
+    let (coin_type, network) = match use_info {
+        Some(x) => (x.get_coin_type(), x.get_network()),
+        None => (CoinType::Bitcoin, Network::MainNet),
+    };
+    let network = match coin_type {
+        CoinType::Bitcoin => Network::MainNet,
+        _ => network,
+    };
+    get_version(&coin_type, &network, ScriptType::P2pkh, is_private_key)
+        .or_else(|| get_version(&CoinType::Bitcoin, &Network::MainNet, ScriptType::P2pkh, is_private_key))
+        .unwrap()
+}
+
+impl TryFrom<&str> for CryptoHDKey {
+    type Error = String;
+
+    fn try_from(extended_key: &str) -> Result<Self, Self::Error> {
+        CryptoHDKey::from_extended_key(extended_key).map(|(hd_key, _)| hd_key)
+    }
 }
 
 impl RegistryItem for CryptoHDKey {
@@ -166,10 +584,13 @@ impl To for CryptoHDKey {
         if self.is_master() {
             map.insert(Value::Integer(IS_MASTER), Value::Bool(self.is_master()));
             map.insert(Value::Integer(KEY_DATA), Value::Bytes(self.key.clone()));
-            map.insert(
-                Value::Integer(CHAIN_CODE),
-                Value::Bytes(self.chain_code.clone().unwrap()),
-            );
+            // the chain code is required for master keys, a malformed key is still encoded without it
+            match &self.chain_code {
+                Some(x) => {
+                    map.insert(Value::Integer(CHAIN_CODE), Value::Bytes(x.clone()));
+                }
+                None => {}
+            }
         } else {
             match self.is_private_key {
                 Some(x) => {
@@ -208,10 +629,11 @@ impl To for CryptoHDKey {
                 }
                 None => {}
             }
-            match &self.children {
+            // the spec allows a single children keypath, the array form is only accepted on decode
+            match self.children.first() {
                 Some(x) => {
                     map.insert(
-                        Value::Integer(ORIGIN),
+                        Value::Integer(CHILDREN),
                         Value::Tag(
                             CryptoKeyPath::get_registry_type().get_tag() as u64,
                             Box::new(x.to_cbor()),
@@ -247,103 +669,212 @@ impl To for CryptoHDKey {
 
     fn to_bytes(&self) -> Vec<u8> {
         let value = self.to_cbor();
-        to_vec(&value).unwrap()
+        encode(&value).unwrap()
     }
 }
 
-impl From<CryptoHDKey> for CryptoHDKey {
-    fn from_cbor(cbor: Value) -> Result<CryptoHDKey, String> {
+impl CryptoHDKey {
+    // like `from_cbor` but also accepts key data and chain code written as hex text
+    pub fn from_cbor_lenient(cbor: Value) -> Result<CryptoHDKey, String> {
+        CryptoHDKey::decode_cbor(cbor, true)
+    }
+
+    pub fn from_bytes_lenient(bytes: Vec<u8>) -> Result<CryptoHDKey, String> {
+        let value = decode(bytes.as_slice())?;
+        CryptoHDKey::from_cbor_lenient(value)
+    }
+
+    // for watch-only callers, master keys carry private key data even without the is_private flag
+    pub fn from_bytes_public_only(bytes: Vec<u8>) -> Result<CryptoHDKey, String> {
+        let hd_key = CryptoHDKey::from_bytes(bytes)?;
+        if hd_key.is_master() || hd_key.is_private_key() {
+            return Err("private key data is not accepted for a public-only crypto-hdkey".to_string());
+        }
+        Ok(hd_key)
+    }
+
+    fn decode_cbor(cbor: Value, lenient: bool) -> Result<CryptoHDKey, String> {
+        let get_bytes = |v: &CborValue| match lenient {
+            true => v.get_bytes_or_hex(),
+            false => v.get_bytes(),
+        };
+        // the value may not have come through `decode`, guard the use_info/origin/children unwrapping too
+        check_depth(&cbor, MAX_DEPTH).map_err(log_decode_error::<CryptoHDKey>)?;
         let value = CborValue::new(cbor);
-        let map = value.get_map()?;
+        let map = value.get_map().map_err(log_decode_error::<CryptoHDKey>)?;
         let is_master = map
             .get_by_integer(IS_MASTER)
             .map(|v| v.get_bool())
-            .transpose()?;
+            .transpose()
+            .map_err(log_field_error::<CryptoHDKey>(IS_MASTER))?;
         match is_master {
+            // a master key only carries key data and chain code, any other field such as
+            // use_info or origin is ignored rather than rejected
             Some(true) => {
                 let key = map
                     .get_by_integer(KEY_DATA)
-                    .map(|v| v.get_bytes())
-                    .transpose()?
-                    .ok_or("key data is required for crypto-hdkey".to_string())?;
+                    .map(get_bytes)
+                    .transpose()
+                    .map_err(log_field_error::<CryptoHDKey>(KEY_DATA))?
+                    .ok_or("key data is required for crypto-hdkey".to_string())
+                    .map_err(log_field_error::<CryptoHDKey>(KEY_DATA))?;
                 let chain_code = map
                     .get_by_integer(CHAIN_CODE)
-                    .map(|v| v.get_bytes())
-                    .transpose()?
+                    .map(get_bytes)
+                    .transpose()
+                    .map_err(log_field_error::<CryptoHDKey>(CHAIN_CODE))?
                     .ok_or(
                         "chain code is required for crypto-hdkey when it is a master key"
                             .to_string(),
-                    )?;
+                    )
+                    .map_err(log_field_error::<CryptoHDKey>(CHAIN_CODE))?;
                 Ok(CryptoHDKey::new_master_key(key, chain_code))
             }
             _ => {
                 let is_private_key = map
                     .get_by_integer(IS_PRIVATE)
                     .map(|v| v.get_bool())
-                    .transpose()?;
+                    .transpose()
+                    .map_err(log_field_error::<CryptoHDKey>(IS_PRIVATE))?;
                 let key = map
                     .get_by_integer(KEY_DATA)
-                    .map(|v| v.get_bytes())
-                    .transpose()?
-                    .ok_or("key data is required for crypto-hdkey".to_string())?;
+                    .map(get_bytes)
+                    .transpose()
+                    .map_err(log_field_error::<CryptoHDKey>(KEY_DATA))?
+                    .ok_or("key data is required for crypto-hdkey".to_string())
+                    .map_err(log_field_error::<CryptoHDKey>(KEY_DATA))?;
                 let chain_code = map
                     .get_by_integer(CHAIN_CODE)
-                    .map(|v| v.get_bytes())
-                    .transpose()?;
+                    .map(get_bytes)
+                    .transpose()
+                    .map_err(log_field_error::<CryptoHDKey>(CHAIN_CODE))?;
                 let use_info = map
                     .get_by_integer(USE_INFO)
                     .map(|v| v.get_tag(CRYPTO_COIN_INFO.get_tag()))
-                    .transpose()?
+                    .transpose()
+                    .map_err(log_field_error::<CryptoHDKey>(USE_INFO))?
                     .map(|v| CryptoCoinInfo::from_cbor(v.get_value().clone()))
-                    .transpose()?;
+                    .transpose()
+                    .map_err(log_field_error::<CryptoHDKey>(USE_INFO))?;
                 let origin = map
                     .get_by_integer(ORIGIN)
                     .map(|v| v.get_tag(CRYPTO_KEYPATH.get_tag()))
-                    .transpose()?
+                    .transpose()
+                    .map_err(log_field_error::<CryptoHDKey>(ORIGIN))?
                     .map(|v| CryptoKeyPath::from_cbor(v.get_value().clone()))
-                    .transpose()?;
-                let children = map
-                    .get_by_integer(CHILDREN)
-                    .map(|v| v.get_tag(CRYPTO_KEYPATH.get_tag()))
-                    .transpose()?
-                    .map(|v| CryptoKeyPath::from_cbor(v.get_value().clone()))
-                    .transpose()?;
+                    .transpose()
+                    .map_err(log_field_error::<CryptoHDKey>(ORIGIN))?;
+                let children = match map.get_by_integer(CHILDREN).map(|v| v.get_value()) {
+                    Some(Value::Array(x)) => x.clone(),
+                    Some(x) => vec![x.clone()],
+                    None => vec![],
+                }
+                .into_iter()
+                .map(|v| {
+                    CborValue::new(v)
+                        .get_tag(CRYPTO_KEYPATH.get_tag())
+                        .and_then(|v| CryptoKeyPath::from_cbor(v.get_value().clone()))
+                })
+                .collect::<Result<Vec<CryptoKeyPath>, String>>()
+                .map_err(log_field_error::<CryptoHDKey>(CHILDREN))?;
                 let parent_fingerprint = map
                     .get_by_integer(PARENT_FINGERPRINT)
                     .map(|v| v.get_integer())
-                    .transpose()?
+                    .transpose()
+                    .map_err(log_field_error::<CryptoHDKey>(PARENT_FINGERPRINT))?
                     .map(|v| u32::to_be_bytes(v as u32));
-                let name = map.get_by_integer(NAME).map(|v| v.get_text()).transpose()?;
-                let note = map.get_by_integer(NOTE).map(|v| v.get_text()).transpose()?;
-                Ok(CryptoHDKey::new_extended_key(
+                let name = map
+                    .get_by_integer(NAME)
+                    .map(|v| v.get_text())
+                    .transpose()
+                    .map_err(log_field_error::<CryptoHDKey>(NAME))?;
+                let note = map
+                    .get_by_integer(NOTE)
+                    .map(|v| v.get_text())
+                    .transpose()
+                    .map_err(log_field_error::<CryptoHDKey>(NOTE))?;
+                let mut hd_key = CryptoHDKey::new_extended_key(
                     is_private_key,
                     key,
                     chain_code,
                     use_info,
                     origin,
-                    children,
+                    None,
                     parent_fingerprint,
                     name,
                     note,
-                ))
+                );
+                hd_key.set_children_paths(children);
+                Ok(hd_key)
             }
         }
     }
 
+}
+
+impl From<CryptoHDKey> for CryptoHDKey {
+    fn from_cbor(cbor: Value) -> Result<CryptoHDKey, String> {
+        CryptoHDKey::decode_cbor(cbor, false)
+    }
+
     fn from_bytes(bytes: Vec<u8>) -> Result<CryptoHDKey, String> {
-        let value: Value = match from_slice(bytes.as_slice()) {
-            Ok(x) => x,
-            Err(e) => return Err(e.to_string()),
-        };
+        let value = decode(bytes.as_slice())?;
         CryptoHDKey::from_cbor(value)
     }
 }
 
+// master keys and private keys carry a zero prefix, public keys a compressed point prefix
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for CryptoHDKey {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let key_data = |u: &mut arbitrary::Unstructured<'a>, prefix: u8| {
+            u.arbitrary::<[u8; 32]>().map(|x| {
+                let mut key = vec![prefix];
+                key.extend_from_slice(&x);
+                key
+            })
+        };
+        if u.arbitrary::<bool>()? {
+            let key = key_data(u, 0)?;
+            return Ok(CryptoHDKey::new_master_key(key, u.arbitrary::<[u8; 32]>()?.to_vec()));
+        }
+        let is_private_key = u.arbitrary::<bool>()?;
+        let prefix = match is_private_key {
+            true => 0,
+            false => *u.choose(&[0x02, 0x03])?,
+        };
+        Ok(CryptoHDKey {
+            is_master: Some(false),
+            is_private_key: Some(is_private_key),
+            key: key_data(u, prefix)?,
+            chain_code: u.arbitrary::<Option<[u8; 32]>>()?.map(|x| x.to_vec()),
+            use_info: u.arbitrary()?,
+            origin: u.arbitrary()?,
+            children: u.arbitrary()?,
+            parent_fingerprint: u.arbitrary()?,
+            name: u.arbitrary()?,
+            note: u.arbitrary()?,
+        })
+    }
+}
+
+// the crypto-hdkey example of the registry spec, 103 bytes, shared by the tests of other modules
+#[cfg(test)]
+pub(crate) const TEST_HD_KEY_HEX: &str = "A5035821026FE2355745BB2DB3630BBC80EF5D58951C963C841F54170BA6E5C12BE7FC12A6045820CED155C72456255881793514EDC5BD9447E7F74ABB88C6D6B6480FD016EE8C8505D90131A1020106D90130A1018A182CF501F501F500F401F4081AE9181CF3";
+
+// a mainnet public key with placeholder key data at `path` under the 73c5da0a fingerprint
+#[cfg(test)]
+pub(crate) fn test_account_key(path: &str) -> CryptoHDKey {
+    let origin = CryptoKeyPath::from_path(path.to_string(), Some([0x73, 0xc5, 0xda, 0x0a])).unwrap();
+    CryptoHDKey::new_mainnet_extended(vec![0x02; 33], vec![0; 32], Some(origin))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::crypto_coin_info::{CoinType, CryptoCoinInfo, Network};
-    use crate::crypto_hd_key::CryptoHDKey;
+    use crate::crypto_hd_key::{test_account_key, CryptoHDKey};
     use crate::crypto_key_path::{CryptoKeyPath, PathComponent};
+    use crate::ext_key_version::ScriptType;
     use crate::traits::{From, To, UR};
     use hex;
     use hex::FromHex;
@@ -437,4 +968,569 @@ mod tests {
         );
         assert_eq!("xpub6H8Qkexp9BdSgEwPAnhiEjp7NMXVEZWoAFWwon5mSwbuPZMfSUTpPwAP1Q2q2kYMRgRQ8udBpEj89wburY1vW7AWDuYpByteGogpB6pPprX", hd_key.get_bip32_key());
     }
+
+    #[test]
+    fn test_decode_master_key_ignores_extra_fields() {
+        let master_key = CryptoHDKey::from_bytes(Vec::from_hex("a401f503582100e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35045820873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d50805d90131a10201").unwrap()).unwrap();
+        assert_eq!(true, master_key.is_master());
+        assert_eq!(true, master_key.get_use_info().is_none());
+        assert_eq!(
+            "A301F503582100E8F32E723DECF4051AEFAC8E2C93C9C5B214313817CDB01A1494B917C8436B35045820873DFF81C02F525623FD1FE5167EAC3A55A049DE3D314BB42EE227FFED37D508",
+            hex::encode(master_key.to_bytes()).to_uppercase()
+        );
+    }
+
+    #[test]
+    fn test_new_testnet_extended() {
+        let key = Vec::from_hex("026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6").unwrap();
+        let chain_code = Vec::from_hex("ced155c72456255881793514edc5bd9447e7f74abb88c6d6b6480fd016ee8c85").unwrap();
+        let testnet_key = CryptoHDKey::new_testnet_extended(key.clone(), chain_code.clone(), None);
+        let use_info = testnet_key.get_use_info().unwrap();
+        assert_eq!(Network::TestNet, use_info.get_network());
+        assert_eq!(CoinType::Bitcoin, use_info.get_coin_type());
+        assert_eq!(false, testnet_key.is_master());
+
+        let mainnet_key = CryptoHDKey::new_mainnet_extended(key, chain_code, None);
+        assert_eq!(Network::MainNet, mainnet_key.get_use_info().unwrap().get_network());
+    }
+
+    #[test]
+    fn test_is_testnet() {
+        let key = Vec::from_hex("026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6").unwrap();
+        let chain_code = Vec::from_hex("ced155c72456255881793514edc5bd9447e7f74abb88c6d6b6480fd016ee8c85").unwrap();
+        assert_eq!(true, CryptoHDKey::new_testnet_extended(key.clone(), chain_code.clone(), None).is_testnet());
+        assert_eq!(false, CryptoHDKey::new_mainnet_extended(key, chain_code, None).is_testnet());
+    }
+
+    #[test]
+    fn test_decode_with_updated_tags() {
+        let hd_key = CryptoHDKey::from_bytes(Vec::from_hex("a5035821026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6045820ced155c72456255881793514edc5bd9447e7f74abb88c6d6b6480fd016ee8c8505d99d71a1020106d99d70a1018a182cf501f501f500f401f4081ae9181cf3").unwrap()).unwrap();
+        assert_eq!(Network::TestNet, hd_key.get_use_info().unwrap().get_network());
+        assert_eq!(Some("44'/1'/1'/0/1".to_string()), hd_key.get_origin().unwrap().get_path());
+        assert_eq!(
+            "A5035821026FE2355745BB2DB3630BBC80EF5D58951C963C841F54170BA6E5C12BE7FC12A6045820CED155C72456255881793514EDC5BD9447E7F74ABB88C6D6B6480FD016EE8C8505D90131A1020106D90130A1018A182CF501F501F500F401F4081AE9181CF3",
+            hex::encode(hd_key.to_bytes()).to_uppercase()
+        );
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_account_xpub() {
+        let master_key = CryptoHDKey::from_bytes(Vec::from_hex("A301F503582100E8F32E723DECF4051AEFAC8E2C93C9C5B214313817CDB01A1494B917C8436B35045820873DFF81C02F525623FD1FE5167EAC3A55A049DE3D314BB42EE227FFED37D508").unwrap()).unwrap();
+        let account = master_key.account_xpub(84, 0, 0).unwrap();
+        assert_eq!(false, account.is_private_key());
+        assert_eq!(
+            "03db2bd5f387c3bf52544ccbb1cdd00ed75604c03829bd64512fa7e916aa3c9de9",
+            hex::encode(account.get_key())
+        );
+        assert_eq!(Some([0x30, 0x20, 0x07, 0xeb]), account.get_parent_fingerprint());
+        let origin = account.get_origin().unwrap();
+        assert_eq!(Some("84'/0'/0'".to_string()), origin.get_path());
+        assert_eq!(Some([0x34, 0x42, 0x19, 0x3e]), origin.get_source_fingerprint());
+        assert_eq!(
+            "xpub6C1HVMz946r433QEjZGpYYWYcspxXXBPys5PBGkmQboRXE6RLfFiStEkKbWKCZaPgDrzZh9nUEunxuiuy6MNdw23du2Ek7GoKYMJVH8eK5E",
+            account.get_bip32_key()
+        );
+        assert!(account.account_xpub(84, 0, 0).is_err());
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_addresses() {
+        let master_key = CryptoHDKey::from_bytes(Vec::from_hex("A301F503582100E8F32E723DECF4051AEFAC8E2C93C9C5B214313817CDB01A1494B917C8436B35045820873DFF81C02F525623FD1FE5167EAC3A55A049DE3D314BB42EE227FFED37D508").unwrap()).unwrap();
+        let account = master_key.account_xpub(84, 1, 0).unwrap();
+        assert_eq!(
+            vec![
+                "tb1q7f0pjwhc3jzzv0w4uurm589506glv2dg2qy7ze".to_string(),
+                "tb1q3jeqwzg70pfkc9k4pvynlmfjlrrghp0c0hkeq0".to_string(),
+            ],
+            account.addresses(false, 0, 2).unwrap()
+        );
+        // no address params for Litecoin and no single key script for the multisig purpose
+        let mut litecoin = account.clone();
+        litecoin.use_info = Some(CryptoCoinInfo::new(Some(CoinType::Litecoin), Some(Network::MainNet)));
+        assert!(litecoin.addresses(false, 0, 2).is_err());
+        let multisig = master_key.account_xpub(48, 1, 0).unwrap();
+        assert!(multisig.addresses(false, 0, 2).is_err());
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_master_fingerprint() {
+        let master_key = CryptoHDKey::from_bytes(Vec::from_hex("A301F503582100E8F32E723DECF4051AEFAC8E2C93C9C5B214313817CDB01A1494B917C8436B35045820873DFF81C02F525623FD1FE5167EAC3A55A049DE3D314BB42EE227FFED37D508").unwrap()).unwrap();
+        assert_eq!([0x34, 0x42, 0x19, 0x3e], master_key.master_fingerprint().unwrap());
+        let account = master_key.account_xpub(84, 0, 0).unwrap();
+        assert_eq!([0x34, 0x42, 0x19, 0x3e], account.master_fingerprint().unwrap());
+        assert!(CryptoHDKey::new_mainnet_extended(vec![0x02; 33], vec![0; 32], None)
+            .master_fingerprint()
+            .is_err());
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_neutered() {
+        let master_key = CryptoHDKey::from_bytes(Vec::from_hex("A301F503582100E8F32E723DECF4051AEFAC8E2C93C9C5B214313817CDB01A1494B917C8436B35045820873DFF81C02F525623FD1FE5167EAC3A55A049DE3D314BB42EE227FFED37D508").unwrap()).unwrap();
+        let public_key = master_key.neutered().unwrap();
+        assert_eq!(false, public_key.is_private_key());
+        assert_eq!(false, public_key.is_master());
+        assert_eq!(master_key.get_chain_code(), public_key.get_chain_code());
+        assert_eq!(
+            "0339a36013301597daef41fbe593a02cc513d0b55527ec2df1050e2e8ff49c85c2",
+            hex::encode(public_key.get_key())
+        );
+        assert_eq!(
+            "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8",
+            public_key.get_bip32_key()
+        );
+    }
+
+    #[test]
+    fn test_children_paths() {
+        let hd_key = CryptoHDKey::from_bytes(Vec::from_hex("a3035821026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6045820ced155c72456255881793514edc5bd9447e7f74abb88c6d6b6480fd016ee8c850782d90130a1018400f480f4d90130a1018401f480f4").unwrap()).unwrap();
+        let children = hd_key.get_children_paths();
+        assert_eq!(2, children.len());
+        assert_eq!(Some("0/*".to_string()), children[0].get_path());
+        assert_eq!(Some("1/*".to_string()), children[1].get_path());
+        assert_eq!(Some("0/*".to_string()), hd_key.get_children().unwrap().get_path());
+        // only the first children path is written back
+        assert_eq!(
+            "a3035821026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6045820ced155c72456255881793514edc5bd9447e7f74abb88c6d6b6480fd016ee8c8507d90130a1018400f480f4",
+            hex::encode(hd_key.to_bytes())
+        );
+
+        let hd_key = CryptoHDKey::from_bytes(Vec::from_hex("a3035821026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6045820ced155c72456255881793514edc5bd9447e7f74abb88c6d6b6480fd016ee8c8507d90130a1018400f480f4").unwrap()).unwrap();
+        assert_eq!(1, hd_key.get_children_paths().len());
+        assert_eq!(
+            "a3035821026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6045820ced155c72456255881793514edc5bd9447e7f74abb88c6d6b6480fd016ee8c8507d90130a1018400f480f4",
+            hex::encode(hd_key.to_bytes())
+        );
+    }
+
+    #[test]
+    fn test_encode_children_key() {
+        // origin under key 6 and children under key 7, the children used to overwrite the origin
+        let hd_key = CryptoHDKey::new_extended_key(
+            None,
+            vec![0x02; 33],
+            Some(vec![0; 32]),
+            None,
+            Some(CryptoKeyPath::from_path("84'/0'/0'".to_string(), None).unwrap()),
+            Some(CryptoKeyPath::from_path("0/*".to_string(), None).unwrap()),
+            None,
+            None,
+            None,
+        );
+        assert_eq!(
+            format!(
+                "a4035821{}045820{}06d90130a101861854f500f500f507d90130a1018400f480f4",
+                "02".repeat(33),
+                "00".repeat(32)
+            ),
+            hex::encode(hd_key.to_bytes())
+        );
+    }
+
+    #[test]
+    fn test_consistency_warnings() {
+        let origin = CryptoKeyPath::from_path("m/0'".to_string(), Some([0x34, 0x42, 0x19, 0x3e])).unwrap();
+        let mut hd_key = CryptoHDKey::new_mainnet_extended(vec![0x02; 33], vec![0; 32], Some(origin));
+        assert_eq!(0, hd_key.consistency_warnings().len());
+
+        hd_key.parent_fingerprint = Some([0x34, 0x42, 0x19, 0x3e]);
+        assert_eq!(0, hd_key.consistency_warnings().len());
+
+        hd_key.parent_fingerprint = Some([0xe9, 0x18, 0x1c, 0xf3]);
+        assert_eq!(
+            vec!["parent fingerprint e9181cf3 does not match source fingerprint 3442193e at depth 1".to_string()],
+            hd_key.consistency_warnings()
+        );
+    }
+
+    #[test]
+    fn test_bip32_key_hardened_index() {
+        // BIP32 test vector 1, chain m/0'
+        let hd_key = CryptoHDKey::new_extended_key(
+            Some(false),
+            Vec::from_hex("035a784662a4a20a65bf6aab9ae98a6c068a81c52e4b032c0fb5400c706cfccc56")
+                .unwrap(),
+            Some(
+                Vec::from_hex("47fdacbd0f1097043b78c63c20c34ef4ed9a111d980047ad16282c7ae6236141")
+                    .unwrap(),
+            ),
+            None,
+            Some(CryptoKeyPath::new(
+                vec![PathComponent::new(Some(0), true).unwrap()],
+                None,
+                None,
+            )),
+            None,
+            Some([0x34, 0x42, 0x19, 0x3e]),
+            None,
+            None,
+        );
+        assert_eq!("xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw", hd_key.get_bip32_key());
+        let raw = bs58::decode(hd_key.get_bip32_key()).with_check(None).into_vec().unwrap();
+        assert_eq!([0x80, 0x00, 0x00, 0x00], raw[9..13]);
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn test_bip32_key_derived_hardened_child() {
+        use crate::crypto::{bip32, secp};
+        let master_key = secp::secret_key(
+            &Vec::from_hex("e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35").unwrap(),
+        )
+        .unwrap();
+        let chain_code =
+            Vec::from_hex("873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508").unwrap();
+        let origin = CryptoKeyPath::from_path("m/0'".to_string(), None).unwrap();
+        let indexes = origin
+            .get_components()
+            .iter()
+            .filter_map(|component| component.get_canonical_index())
+            .collect::<Vec<u32>>();
+        let (child_key, child_chain_code) =
+            bip32::derive_private_path(&master_key, &chain_code, &indexes).unwrap();
+        let mut key = vec![0];
+        key.extend_from_slice(&child_key.secret_bytes());
+        let hd_key = CryptoHDKey::new_extended_key(
+            Some(true),
+            key,
+            Some(child_chain_code),
+            None,
+            Some(origin),
+            None,
+            Some([0x34, 0x42, 0x19, 0x3e]),
+            None,
+            None,
+        );
+        assert_eq!("xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7", hd_key.get_bip32_key());
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary() {
+        use arbitrary::{Arbitrary, Unstructured};
+        // deterministic input so failures are reproducible
+        let mut state: u32 = 0x12345678;
+        let data = (0..65536)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect::<Vec<u8>>();
+        let mut u = Unstructured::new(&data);
+        for _ in 0..100 {
+            let hd_key = CryptoHDKey::arbitrary(&mut u).unwrap();
+            assert_eq!(33, hd_key.get_key().len());
+            let decoded = CryptoHDKey::from_bytes(hd_key.to_bytes()).unwrap();
+            assert_eq!(hd_key.get_key(), decoded.get_key());
+            CryptoKeyPath::arbitrary(&mut u).unwrap().to_bytes();
+            CryptoCoinInfo::arbitrary(&mut u).unwrap().to_bytes();
+        }
+    }
+
+    #[test]
+    fn test_same_key() {
+        let bytes = Vec::from_hex("A5035821026FE2355745BB2DB3630BBC80EF5D58951C963C841F54170BA6E5C12BE7FC12A6045820CED155C72456255881793514EDC5BD9447E7F74ABB88C6D6B6480FD016EE8C8505D90131A1020106D90130A1018A182CF501F501F500F401F4081AE9181CF3").unwrap();
+        let hd_key = CryptoHDKey::from_bytes(bytes.clone()).unwrap();
+        let mut named = CryptoHDKey::from_bytes(bytes).unwrap();
+        named.name = Some("Keystone".to_string());
+        named.note = Some("account.standard".to_string());
+        named.use_info = None;
+        assert_eq!(true, hd_key.same_key(&named));
+
+        named.origin = Some(CryptoKeyPath::from_path("m/44'/1'/1'/0/2".to_string(), None).unwrap());
+        assert_eq!(false, hd_key.same_key(&named));
+
+        let mut other = hd_key.clone();
+        other.parent_fingerprint = None;
+        assert_eq!(false, hd_key.same_key(&other));
+
+        // an explicit depth on the origin agrees with the serialized xpub either way
+        let mut explicit_depth = hd_key.clone();
+        let origin = hd_key.get_origin().unwrap();
+        explicit_depth.origin = Some(CryptoKeyPath::new(origin.get_components(), origin.get_source_fingerprint(), Some(7)));
+        assert_eq!(
+            hd_key.get_bip32_key() == explicit_depth.get_bip32_key(),
+            hd_key.same_key(&explicit_depth)
+        );
+
+        // the same key material as a Litecoin key serializes as Ltub
+        let mut litecoin = hd_key.clone();
+        litecoin.use_info = Some(CryptoCoinInfo::new(Some(CoinType::Litecoin), Some(Network::MainNet)));
+        assert_ne!(hd_key.get_bip32_key(), litecoin.get_bip32_key());
+        assert_eq!(false, hd_key.same_key(&litecoin));
+    }
+
+    #[test]
+    fn test_try_new() {
+        let key = Vec::from_hex("026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6").unwrap();
+        let chain_code = Vec::from_hex("ced155c72456255881793514edc5bd9447e7f74abb88c6d6b6480fd016ee8c85").unwrap();
+        let new = |is_private_key: Option<bool>, key: Vec<u8>, chain_code: Option<Vec<u8>>| {
+            CryptoHDKey::try_new(is_private_key, key, chain_code, None, None, None, None, None, None)
+        };
+        assert!(new(None, key.clone(), Some(chain_code.clone())).is_ok());
+        assert!(new(Some(false), key.clone(), None).is_ok());
+        assert!(new(None, vec![], None).is_err());
+        assert!(new(Some(true), key.clone(), None).is_err());
+        assert!(new(None, key.clone(), Some(vec![0; 31])).is_err());
+        assert_eq!(
+            "invalid public key prefix 0x04, expected 0x02 or 0x03",
+            new(None, [vec![0x04], key[1..].to_vec()].concat(), None).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_default_to_bytes() {
+        let hd_key = CryptoHDKey::default();
+        assert_eq!("a10340", hex::encode(hd_key.to_bytes()));
+
+        let mut master_key = CryptoHDKey::default();
+        master_key.is_master = Some(true);
+        assert_eq!("a201f50340", hex::encode(master_key.to_bytes()));
+    }
+
+    #[test]
+    fn test_bip32_key_coin_versions() {
+        let hd_key = |coin_type: CoinType| {
+            CryptoHDKey::new_extended_key(
+                Some(false),
+                Vec::from_hex("035a784662a4a20a65bf6aab9ae98a6c068a81c52e4b032c0fb5400c706cfccc56")
+                    .unwrap(),
+                Some(
+                    Vec::from_hex("47fdacbd0f1097043b78c63c20c34ef4ed9a111d980047ad16282c7ae6236141")
+                        .unwrap(),
+                ),
+                Some(CryptoCoinInfo::new(Some(coin_type), Some(Network::MainNet))),
+                Some(CryptoKeyPath::new(
+                    vec![PathComponent::new(Some(0), true).unwrap()],
+                    None,
+                    None,
+                )),
+                None,
+                Some([0x34, 0x42, 0x19, 0x3e]),
+                None,
+                None,
+            )
+        };
+        assert_eq!("Ltub2UhtRiSfp82berwLEKkB34QBEt2TUdCDCu4WNzGumvAMwYsxfWjULKsXhADxqy3cuDu3TnqoKJr1xmB8Wb2qzthWAtbb4CutpXPuSU1YMgG", hd_key(CoinType::Litecoin).get_bip32_key());
+        assert_eq!("xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw", hd_key(CoinType::Bitcoin).get_bip32_key());
+        assert!(hd_key(CoinType::Dogecoin).get_bip32_key().starts_with("dgub"));
+    }
+
+    #[test]
+    fn test_decode_lenient_hex_text() {
+        // key data and chain code of the spec vector written as text strings
+        let bytes = Vec::from_hex("a3037842303236666532333535373435626232646233363330626263383065663564353839353163393633633834316635343137306261366535633132626537666331326136047840636564313535633732343536323535383831373933353134656463356264393434376537663734616262383863366436623634383066643031366565386338350801").unwrap();
+        assert!(CryptoHDKey::from_bytes(bytes.clone()).is_err());
+        let hd_key = CryptoHDKey::from_bytes_lenient(bytes).unwrap();
+        assert_eq!(
+            "026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6",
+            hex::encode(hd_key.get_key())
+        );
+        assert_eq!(
+            "ced155c72456255881793514edc5bd9447e7f74abb88c6d6b6480fd016ee8c85",
+            hex::encode(hd_key.get_chain_code().unwrap())
+        );
+        assert!(CryptoHDKey::from_bytes_lenient(Vec::from_hex("a103627a7a").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_to_extended_key_bytes() {
+        let hd_key = CryptoHDKey::from_bytes(Vec::from_hex("A5035821026FE2355745BB2DB3630BBC80EF5D58951C963C841F54170BA6E5C12BE7FC12A6045820CED155C72456255881793514EDC5BD9447E7F74ABB88C6D6B6480FD016EE8C8505D90131A1020106D90130A1018A182CF501F501F500F401F4081AE9181CF3").unwrap()).unwrap();
+        let bytes = hd_key.to_extended_key_bytes().unwrap();
+        assert_eq!(78, bytes.len());
+        assert_eq!("0488b21e05e9181cf300000001", hex::encode(&bytes[..13]));
+        assert_eq!(
+            hd_key.get_bip32_key(),
+            bs58::encode(bytes).with_check().into_string()
+        );
+
+        let mut short_key = hd_key.clone();
+        short_key.key = vec![0x02; 32];
+        assert!(short_key.to_extended_key_bytes().is_err());
+    }
+
+    #[test]
+    fn test_malformed_chain_code() {
+        let hd_key = CryptoHDKey::from_bytes(Vec::from_hex("A5035821026FE2355745BB2DB3630BBC80EF5D58951C963C841F54170BA6E5C12BE7FC12A6045820CED155C72456255881793514EDC5BD9447E7F74ABB88C6D6B6480FD016EE8C8505D90131A1020106D90130A1018A182CF501F501F500F401F4081AE9181CF3").unwrap()).unwrap();
+        let mut short_chain_code = hd_key.clone();
+        short_chain_code.chain_code = Some(hd_key.get_chain_code().unwrap()[..16].to_vec());
+        assert_eq!(
+            Err("invalid chain code length 16, expected 32 bytes".to_string()),
+            short_chain_code.to_extended_key_bytes()
+        );
+        assert!(short_chain_code.get_slip132_key(ScriptType::P2wpkh).is_err());
+        // the key keeps its offset behind a zero padded chain code
+        let raw = bs58::decode(short_chain_code.get_bip32_key()).with_check(None).into_vec().unwrap();
+        assert_eq!(78, raw.len());
+        assert_eq!(hd_key.get_chain_code().unwrap()[..16], raw[13..29]);
+        assert_eq!(vec![0; 16], raw[29..45].to_vec());
+        assert_eq!(hd_key.get_key(), raw[45..].to_vec());
+    }
+
+    #[test]
+    fn test_with_origin() {
+        // BIP32 test vector 1, chain m/0'
+        let xpub = "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw";
+        let data = bs58::decode(xpub).with_check(None).into_vec().unwrap();
+        let hd_key = CryptoHDKey::new_extended_key(
+            Some(false),
+            data[45..78].to_vec(),
+            Some(data[13..45].to_vec()),
+            None,
+            None,
+            None,
+            Some([0x34, 0x42, 0x19, 0x3e]),
+            None,
+            None,
+        );
+        assert_ne!(xpub, hd_key.get_bip32_key());
+
+        let origin = CryptoKeyPath::from_path("m/0'".to_string(), Some([0x34, 0x42, 0x19, 0x3e])).unwrap();
+        let hd_key = hd_key.with_origin(origin.clone());
+        assert_eq!(xpub, hd_key.get_bip32_key());
+        assert_eq!(Some("0'".to_string()), hd_key.get_origin().unwrap().get_path());
+
+        let mut hd_key = hd_key;
+        hd_key.set_origin(CryptoKeyPath::from_path("m/0'/1".to_string(), None).unwrap());
+        assert_ne!(xpub, hd_key.get_bip32_key());
+        hd_key.set_origin(origin);
+        assert_eq!(xpub, hd_key.get_bip32_key());
+    }
+
+    #[test]
+    fn test_from_extended_key() {
+        // m/0' of BIP32 test vector 1 with its SLIP-132 prefixes
+        let xpub = "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw";
+        let ypub = "ypub6T73GjuZ5NG5FnrWUCXoPHPTL3rLfTfZzjNkLJRgnRhYGH4PGAQJ8k3EMVfXBUJHiecGd93ovwZBjxRaKPMQxCbgk6QYyRyLbkhCvXJ8PtA";
+        let zpub = "zpub6mwJaQaUE3oZ763dJZKRbNUxW1znc5f4uqty7hKaAS5RKNscWpZrkohNNhd7BNxD8Hj5NceNPbujdF3935mRkSHHcS6yZLnpsUkrK1XoMLr";
+
+        let (hd_key, script_type) = CryptoHDKey::from_extended_key(zpub).unwrap();
+        assert_eq!(ScriptType::P2wpkh, script_type);
+        assert_eq!(zpub, hd_key.get_slip132_key(script_type).unwrap());
+        assert_eq!(xpub, hd_key.get_bip32_key());
+        assert_eq!(Some(1), hd_key.get_depth());
+        assert_eq!(Some([0x34, 0x42, 0x19, 0x3e]), hd_key.get_parent_fingerprint());
+        assert_eq!(CoinType::Bitcoin, hd_key.get_use_info().unwrap().get_coin_type());
+
+        let (hd_key, script_type) = CryptoHDKey::from_extended_key(ypub).unwrap();
+        assert_eq!(ScriptType::P2shP2wpkh, script_type);
+        assert_eq!(ypub, hd_key.get_slip132_key(script_type).unwrap());
+
+        let hd_key = CryptoHDKey::try_from(xpub).unwrap();
+        assert_eq!(xpub, hd_key.get_bip32_key());
+        assert_eq!(zpub, hd_key.get_slip132_key(ScriptType::P2wpkh).unwrap());
+
+        // the same key at depth 3
+        let deeper = "xpub6C33geGgMJ4b82vKhpfhoTpdkXXn8HtyDR7LJdH8kJ3U518n65FR63YHqSFcfSTJCQMm9oNfzMcmDcndLQTzGo7x34MynBkFyVJTdw64erz";
+        let hd_key = CryptoHDKey::try_from(deeper).unwrap();
+        assert_eq!(Some(3), hd_key.get_origin().unwrap().get_depth());
+        assert_eq!(deeper, hd_key.get_bip32_key());
+
+        assert!(CryptoHDKey::try_from("xpub").is_err());
+        assert!(CryptoHDKey::try_from(&zpub[..zpub.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_full_key_path() {
+        let origin = CryptoKeyPath::from_path("m/84'/0'/0'".to_string(), Some([0x73, 0xc5, 0xda, 0x0a])).unwrap();
+        let mut hd_key = CryptoHDKey::new_mainnet_extended(vec![0x02; 33], vec![0; 32], Some(origin));
+        assert_eq!(Some("84'/0'/0'".to_string()), hd_key.full_key_path().unwrap().get_path());
+
+        hd_key.set_children_paths(vec![CryptoKeyPath::from_path("0/*".to_string(), None).unwrap()]);
+        let path = hd_key.full_key_path().unwrap();
+        assert_eq!(Some("84'/0'/0'/0/*".to_string()), path.get_path());
+        assert_eq!(Some([0x73, 0xc5, 0xda, 0x0a]), path.get_source_fingerprint());
+
+        assert!(CryptoHDKey::new_mainnet_extended(vec![0x02; 33], vec![0; 32], None).full_key_path().is_none());
+    }
+
+    #[test]
+    fn test_from_bytes_public_only() {
+        let public_key = CryptoHDKey::new_mainnet_extended(vec![0x02; 33], vec![0; 32], None);
+        assert!(CryptoHDKey::from_bytes_public_only(public_key.to_bytes()).is_ok());
+
+        let private_key = CryptoHDKey::new_extended_key(
+            Some(true),
+            vec![0; 33],
+            Some(vec![0; 32]),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(
+            "private key data is not accepted for a public-only crypto-hdkey",
+            CryptoHDKey::from_bytes_public_only(private_key.to_bytes()).unwrap_err()
+        );
+        let master_key = CryptoHDKey::new_master_key(vec![0; 33], vec![0; 32]);
+        assert!(CryptoHDKey::from_bytes_public_only(master_key.to_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_from_bytes_strict() {
+        let bytes = CryptoHDKey::new_mainnet_extended(vec![0x02; 33], vec![0; 32], None).to_bytes();
+        assert!(CryptoHDKey::from_bytes_strict(bytes.clone()).is_ok());
+        // bump the map length and append a second key data entry
+        let mut duplicated = bytes.clone();
+        duplicated[0] += 1;
+        duplicated.extend([0x03, 0x58, 0x21]);
+        duplicated.extend([0x03; 33]);
+        assert_eq!(vec![0x03; 33], CryptoHDKey::from_bytes(duplicated.clone()).unwrap().get_key());
+        assert!(CryptoHDKey::from_bytes_strict(duplicated)
+            .unwrap_err()
+            .starts_with("duplicate key in cbor map"));
+    }
+
+    #[test]
+    fn test_parent_fingerprint_leading_zeros() {
+        let hd_key = CryptoHDKey::new_extended_key(
+            None,
+            vec![0x02; 33],
+            Some(vec![0; 32]),
+            None,
+            None,
+            None,
+            Some([0x00, 0x12, 0x34, 0x56]),
+            None,
+            None,
+        );
+        let bytes = hd_key.to_bytes();
+        assert!(hex::encode(&bytes).ends_with("081a00123456"));
+        let decoded = CryptoHDKey::from_bytes(bytes).unwrap();
+        assert_eq!(Some([0x00, 0x12, 0x34, 0x56]), decoded.get_parent_fingerprint());
+
+        // a producer writing the fingerprint as the smallest integer, 0x0000002a as 0x182a
+        let bytes = format!("a3035821{}045820{}08182a", "02".repeat(33), "00".repeat(32));
+        let decoded = CryptoHDKey::from_bytes(Vec::from_hex(bytes).unwrap()).unwrap();
+        assert_eq!(Some([0x00, 0x00, 0x00, 0x2a]), decoded.get_parent_fingerprint());
+    }
+
+    #[test]
+    fn test_is_account_level() {
+        assert_eq!(true, test_account_key("m/84'/0'/0'").is_account_level());
+        assert_eq!(false, test_account_key("m/84'/0'/0'/0/0").is_account_level());
+        assert_eq!(false, test_account_key("m/84'/0'/0").is_account_level());
+        assert_eq!(false, CryptoHDKey::new_master_key(vec![0; 33], vec![0; 32]).is_account_level());
+    }
+
+    #[test]
+    fn test_change_indexes() {
+        let children = |paths: &[&str]| {
+            paths
+                .iter()
+                .map(|path| CryptoKeyPath::from_path(path.to_string(), None).unwrap())
+                .collect::<Vec<CryptoKeyPath>>()
+        };
+        let mut hd_key = CryptoHDKey::new_mainnet_extended(vec![0x02; 33], vec![0; 32], None);
+        assert_eq!(Vec::<u32>::new(), hd_key.change_indexes());
+        hd_key.set_children_paths(children(&["0/*"]));
+        assert_eq!(vec![0], hd_key.change_indexes());
+        hd_key.set_children_paths(children(&["0/*", "1/*"]));
+        assert_eq!(vec![0, 1], hd_key.change_indexes());
+    }
 }
@@ -1,8 +1,8 @@
-use crate::cbor_value::CborValue;
+use crate::cbor_value::{log_decode_error, log_field_error, CborValue};
 use crate::registry_types::{RegistryType, CRYPTO_KEYPATH};
 use crate::traits::{From, RegistryItem, To};
 use crate::types::Fingerprint;
-use serde_cbor::{from_slice, to_vec, Value};
+use crate::cbor::{decode, encode, Value};
 use std::collections::BTreeMap;
 
 const COMPONENTS: i128 = 1;
@@ -47,7 +47,7 @@ impl PathComponent {
 
     pub fn get_canonical_index(&self) -> Option<u32> {
         self.get_index().map(|x| match self.is_hardened() {
-            true => x + PathComponent::HARDEN_BIT,
+            true => x | PathComponent::HARDEN_BIT,
             false => x,
         })
     }
@@ -83,6 +83,19 @@ impl CryptoKeyPath {
     pub fn get_components(&self) -> Vec<PathComponent> {
         self.components.clone()
     }
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+    // (index, hardened) per component, the index is None for a wildcard
+    pub fn as_pairs(&self) -> Vec<(Option<u32>, bool)> {
+        self.components
+            .iter()
+            .map(|component| (component.get_index(), component.is_hardened()))
+            .collect()
+    }
     pub fn get_source_fingerprint(&self) -> Option<Fingerprint> {
         self.source_fingerprint.clone()
     }
@@ -109,9 +122,22 @@ impl CryptoKeyPath {
         )
     }
 
+    // the bracketed key origin of a descriptor, e.g. [d34db33f/84'/0'/0']
+    pub fn to_key_origin_string(&self, fingerprint: Fingerprint) -> String {
+        match self.get_path() {
+            Some(path) => format!("[{}/{}]", hex::encode(fingerprint), path),
+            None => format!("[{}]", hex::encode(fingerprint)),
+        }
+    }
+
     pub fn from_path(path: String, fingerprint: Option<Fingerprint>) -> Result<Self, String> {
         let remove_prefix = path.replace("M/", "").replace("m/", "");
         let chunks = remove_prefix.split('/').map(|split| {
+            match split {
+                "*" => return Ok(PathComponent { hardened: false, index: None, wildcard: true }),
+                "*'" => return Ok(PathComponent { hardened: true, index: None, wildcard: true }),
+                _ => {}
+            }
             match split.chars().last() {
                 Some('\'') => {
                     let mut remove_quote = split.to_string();
@@ -131,6 +157,59 @@ impl CryptoKeyPath {
         }).collect::<Result<Vec<PathComponent>, String>>()?;
         Ok(CryptoKeyPath { components: chunks, source_fingerprint: fingerprint, depth: None })
     }
+
+    pub fn is_hardened_only(&self) -> bool {
+        self.components.iter().all(|component| component.is_hardened())
+    }
+
+    pub fn has_wildcard(&self) -> bool {
+        self.components.iter().any(|component| component.is_wildcard())
+    }
+
+    pub fn is_fully_concrete(&self) -> bool {
+        !self.has_wildcard()
+    }
+
+    // drops the last component, the source fingerprint is kept and an explicit depth is decremented
+    pub fn parent(&self) -> Option<CryptoKeyPath> {
+        let (_, components) = self.components.split_last()?;
+        Some(CryptoKeyPath {
+            components: components.to_vec(),
+            source_fingerprint: self.source_fingerprint,
+            depth: self.depth.map(|depth| depth.saturating_sub(1)),
+        })
+    }
+
+    // compares only the components, source fingerprint and depth are ignored
+    pub fn same_path(&self, other: &Self) -> bool {
+        self.components.len() == other.components.len()
+            && self
+                .components
+                .iter()
+                .zip(other.components.iter())
+                .all(|(a, b)| {
+                    a.get_index() == b.get_index()
+                        && a.is_hardened() == b.is_hardened()
+                        && a.is_wildcard() == b.is_wildcard()
+                })
+    }
+
+    // `*` in the template matches any index at that position, the hardened marker still has to match
+    pub fn matches_template(&self, template: &str) -> bool {
+        let template = match CryptoKeyPath::from_path(template.to_string(), None) {
+            Ok(x) => x,
+            Err(_) => return false,
+        };
+        self.components.len() == template.components.len()
+            && self
+                .components
+                .iter()
+                .zip(template.components.iter())
+                .all(|(component, expected)| {
+                    component.is_hardened() == expected.is_hardened()
+                        && (expected.is_wildcard() || component.get_index() == expected.get_index())
+                })
+    }
 }
 
 impl To for CryptoKeyPath {
@@ -167,7 +246,7 @@ impl To for CryptoKeyPath {
 
     fn to_bytes(&self) -> Vec<u8> {
         let value = self.to_cbor();
-        to_vec(&value).unwrap()
+        encode(&value).unwrap()
     }
 }
 
@@ -180,37 +259,49 @@ impl RegistryItem for CryptoKeyPath {
 impl From<CryptoKeyPath> for CryptoKeyPath {
     fn from_cbor(cbor: Value) -> Result<CryptoKeyPath, String> {
         let value = CborValue::new(cbor);
-        let map = value.get_map()?;
+        let map = value.get_map().map_err(log_decode_error::<CryptoKeyPath>)?;
         let components = map
             .get_by_integer(COMPONENTS)
-            .map_or(Ok(vec![]), |v| v.get_array())?
+            .map_or(Ok(vec![]), |v| v.get_array())
+            .map_err(log_field_error::<CryptoKeyPath>(COMPONENTS))?
             .iter()
             .map(|v| v.get_value().clone())
-            .collect::<Vec<Value>>()
-            .chunks(2)
-            .map(|chunk| match chunk.clone() {
-                [Value::Array(_), Value::Bool(hardened)] => Ok(PathComponent {
-                    index: None,
-                    wildcard: true,
-                    hardened: hardened.clone(),
-                }),
-                [Value::Integer(x), Value::Bool(hardened)] => Ok(PathComponent {
-                    index: Some(x.clone() as u32),
-                    wildcard: false,
-                    hardened: hardened.clone(),
-                }),
-                x => Err(format!("Unexpected value when parsing components: {:?}", x)),
-            })
-            .collect::<Result<Vec<PathComponent>, String>>()?;
+            .collect::<Vec<Value>>();
+        // the spec uses a flat [index, hardened, ...] array, some producers nest each pair instead. the
+        // second element tells them apart, a hardened flag in the flat form and a pair when nested
+        let is_flat = components.is_empty() || matches!(components.get(1), Some(Value::Bool(_)));
+        let components = match is_flat {
+            false => components
+                .iter()
+                .map(|pair| match pair {
+                    Value::Array(x) if x.len() == 2 => parse_component(&x[0], &x[1]),
+                    x => Err(format!(
+                        "Unexpected value when parsing components, expected an [index, hardened] pair: {:?}",
+                        x
+                    )),
+                })
+                .collect::<Result<Vec<PathComponent>, String>>(),
+            true if components.len() % 2 != 0 => Err(format!(
+                "Unexpected number of values when parsing components, expected index and hardened pairs: {}",
+                components.len()
+            )),
+            true => components
+                .chunks(2)
+                .map(|chunk| parse_component(&chunk[0], &chunk[1]))
+                .collect::<Result<Vec<PathComponent>, String>>(),
+        }
+        .map_err(log_field_error::<CryptoKeyPath>(COMPONENTS))?;
         let source_fingerprint = map
             .get_by_integer(SOURCE_FINGERPRINT)
             .map(|v| v.get_integer())
-            .transpose()?
+            .transpose()
+            .map_err(log_field_error::<CryptoKeyPath>(SOURCE_FINGERPRINT))?
             .map(|v| u32::to_be_bytes(v as u32));
         let depth = map
             .get_by_integer(DEPTH)
             .map(|v| v.get_integer())
-            .transpose()?
+            .transpose()
+            .map_err(log_field_error::<CryptoKeyPath>(DEPTH))?
             .map(|v| v as u32);
         Ok(CryptoKeyPath {
             components,
@@ -220,10 +311,230 @@ impl From<CryptoKeyPath> for CryptoKeyPath {
     }
 
     fn from_bytes(bytes: Vec<u8>) -> Result<CryptoKeyPath, String> {
-        let value: Value = match from_slice(bytes.as_slice()) {
-            Ok(x) => x,
-            Err(e) => return Err(e.to_string()),
-        };
+        let value = decode(bytes.as_slice())?;
         CryptoKeyPath::from_cbor(value)
     }
 }
+
+fn parse_component(index: &Value, hardened: &Value) -> Result<PathComponent, String> {
+    match (index, hardened) {
+        // [] is a wildcard. PathComponent has no range, a [low, high] range is read as a wildcard too
+        (Value::Array(_), Value::Bool(hardened)) => PathComponent::new(None, *hardened),
+        (Value::Integer(x), Value::Bool(hardened)) => {
+            let index = u32::try_from(*x).map_err(|_| format!("Invalid index {}", x))?;
+            PathComponent::new(Some(index), *hardened)
+        }
+        x => Err(format!("Unexpected value when parsing components: {:?}", x)),
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for PathComponent {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let index = match u.arbitrary::<bool>()? {
+            true => None,
+            false => Some(u.int_in_range(0..=PathComponent::HARDEN_BIT - 1)?),
+        };
+        Ok(PathComponent {
+            index,
+            wildcard: index.is_none(),
+            hardened: u.arbitrary()?,
+        })
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for CryptoKeyPath {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(CryptoKeyPath {
+            components: u.arbitrary()?,
+            source_fingerprint: u.arbitrary()?,
+            depth: u.arbitrary::<Option<u8>>()?.map(u32::from),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto_key_path::{CryptoKeyPath, PathComponent};
+    use crate::traits::{From, To};
+    use hex::FromHex;
+
+    #[test]
+    fn test_canonical_index() {
+        assert_eq!(Some(0x80000000), PathComponent::new(Some(0), true).unwrap().get_canonical_index());
+        assert_eq!(Some(0xffffffff), PathComponent::new(Some(0x7fffffff), true).unwrap().get_canonical_index());
+        assert_eq!(Some(44), PathComponent::new(Some(44), false).unwrap().get_canonical_index());
+        assert_eq!(None, PathComponent::new(None, true).unwrap().get_canonical_index());
+    }
+
+    #[test]
+    fn test_predicates() {
+        let account = CryptoKeyPath::from_path("m/84'/0'/0'".to_string(), None).unwrap();
+        assert_eq!(true, account.is_hardened_only());
+        assert_eq!(false, account.has_wildcard());
+        assert_eq!(true, account.is_fully_concrete());
+
+        let receive = CryptoKeyPath::from_path("0/*".to_string(), None).unwrap();
+        assert_eq!(Some("0/*".to_string()), receive.get_path());
+        assert_eq!(false, receive.is_hardened_only());
+        assert_eq!(true, receive.has_wildcard());
+        assert_eq!(false, receive.is_fully_concrete());
+
+        let address = CryptoKeyPath::from_path("m/84'/0'/0'/0/0".to_string(), None).unwrap();
+        assert_eq!(false, address.is_hardened_only());
+        assert_eq!(false, address.has_wildcard());
+        assert_eq!(true, address.is_fully_concrete());
+    }
+
+    #[test]
+    fn test_len() {
+        let path = CryptoKeyPath::from_path("m/44'/1'/1'/0/1".to_string(), None).unwrap();
+        assert_eq!(5, path.len());
+        assert_eq!(false, path.is_empty());
+        assert_eq!(true, CryptoKeyPath::new(vec![], None, None).is_empty());
+    }
+
+    #[test]
+    fn test_matches_template() {
+        let template = "m/48'/0'/*'/2'";
+        let path = |path: &str| CryptoKeyPath::from_path(path.to_string(), None).unwrap();
+        assert_eq!(true, path("m/48'/0'/0'/2'").matches_template(template));
+        assert_eq!(true, path("m/48'/0'/7'/2'").matches_template(template));
+        assert_eq!(false, path("m/48'/0'/0'/1'").matches_template(template));
+        assert_eq!(false, path("m/48'/0'/0/2'").matches_template(template));
+        assert_eq!(false, path("m/48'/0'/0'").matches_template(template));
+        assert_eq!(false, path("m/48'/0'/0'/2'").matches_template("m/48'/x'"));
+    }
+
+    #[test]
+    fn test_same_path() {
+        let path = CryptoKeyPath::from_path("m/44'/60'/0'/0/0".to_string(), Some([0xf2, 0x3f, 0x9f, 0xd2])).unwrap();
+        let other = CryptoKeyPath::new(path.get_components(), Some([0x12, 0x34, 0x56, 0x78]), Some(5));
+        let bare = CryptoKeyPath::from_path("m/44'/60'/0'/0/0".to_string(), None).unwrap();
+        assert_eq!(true, path.same_path(&other));
+        assert_eq!(true, path.same_path(&bare));
+        assert_eq!(false, path.same_path(&CryptoKeyPath::from_path("m/44'/60'/0'/0/1".to_string(), None).unwrap()));
+        assert_eq!(false, path.same_path(&CryptoKeyPath::from_path("m/44'/60'/0'/0'/0".to_string(), None).unwrap()));
+        assert_eq!(false, path.same_path(&CryptoKeyPath::from_path("m/44'/60'/0'/0".to_string(), None).unwrap()));
+    }
+
+    #[test]
+    fn test_decode_component_layouts() {
+        let decode = |data: &str| CryptoKeyPath::from_bytes(Vec::from_hex(data).unwrap());
+        // flat [44, true, 1, true, 1, true, 0, false, [], false]
+        let flat = decode("a1018a182cf501f501f500f480f4").unwrap();
+        assert_eq!(Some("44'/1'/1'/0/*".to_string()), flat.get_path());
+        // the same path as [[44, true], [1, true], [1, true], [0, false], [[], false]]
+        let pairs = decode("a1018582182cf58201f58201f58200f48280f4").unwrap();
+        assert_eq!(Some("44'/1'/1'/0/*".to_string()), pairs.get_path());
+        assert_eq!(true, flat.same_path(&pairs));
+
+        assert!(decode("a10183182cf501").is_err());
+        assert!(decode("a10182182c01").is_err());
+        assert!(decode("a1018282182cf58101").is_err());
+        assert!(decode("a101821a80000000f5").is_err());
+    }
+
+    #[test]
+    fn test_decode_range_component() {
+        let decode = |data: &str| CryptoKeyPath::from_bytes(Vec::from_hex(data).unwrap());
+        // flat [[1, 5], false, 0, false], the range must not be mistaken for a nested pair
+        let path = decode("a10184820105f400f4").unwrap();
+        assert_eq!(2, path.len());
+        assert_eq!(true, path.get_components()[0].is_wildcard());
+        assert_eq!(Some(0), path.get_components()[1].get_index());
+        // nested [[[1, 5], false], [0, false]]
+        let path = decode("a1018282820105f48200f4").unwrap();
+        assert_eq!(2, path.len());
+        assert_eq!(true, path.get_components()[0].is_wildcard());
+    }
+
+    #[test]
+    fn test_parent() {
+        let path = CryptoKeyPath::from_path("m/84'/0'/0'/0/0".to_string(), Some([0x73, 0xc5, 0xda, 0x0a])).unwrap();
+        let parent = path.parent().unwrap();
+        assert_eq!(Some("84'/0'/0'/0".to_string()), parent.get_path());
+        assert_eq!(Some([0x73, 0xc5, 0xda, 0x0a]), parent.get_source_fingerprint());
+        let with_depth = CryptoKeyPath::new(path.get_components(), None, Some(5));
+        assert_eq!(Some(4), with_depth.parent().unwrap().get_depth());
+        assert!(CryptoKeyPath::new(vec![], None, None).parent().is_none());
+    }
+
+    #[test]
+    fn test_encode_with_fingerprint_and_depth() {
+        let path = CryptoKeyPath::new(
+            vec![
+                PathComponent::new(Some(44), true).unwrap(),
+                PathComponent::new(Some(0), true).unwrap(),
+                PathComponent::new(Some(0), true).unwrap(),
+            ],
+            Some([0x78, 0x23, 0x08, 0x04]),
+            Some(3),
+        );
+        assert_eq!(
+            "a30186182cf500f500f5021a782308040303",
+            hex::encode(path.to_bytes())
+        );
+    }
+
+    #[test]
+    fn test_decode_with_fingerprint_and_depth() {
+        let path = CryptoKeyPath::from_bytes(
+            Vec::from_hex("a30186182cf500f500f5021a782308040303").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(Some("44'/0'/0'".to_string()), path.get_path());
+        assert_eq!(Some([0x78, 0x23, 0x08, 0x04]), path.get_source_fingerprint());
+        assert_eq!(Some(3), path.get_depth());
+
+        // fingerprint only, as in an account key origin
+        let path = CryptoKeyPath::from_bytes(
+            Vec::from_hex("a20186182cf500f500f5021a78230804").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(Some([0x78, 0x23, 0x08, 0x04]), path.get_source_fingerprint());
+        assert_eq!(None, path.get_depth());
+    }
+
+    #[test]
+    fn test_to_key_origin_string() {
+        let path = CryptoKeyPath::from_path("m/84'/0'/0'".to_string(), None).unwrap();
+        assert_eq!("[d34db33f/84'/0'/0']", path.to_key_origin_string([0xd3, 0x4d, 0xb3, 0x3f]));
+        let empty = CryptoKeyPath::new(vec![], None, None);
+        assert_eq!("[d34db33f]", empty.to_key_origin_string([0xd3, 0x4d, 0xb3, 0x3f]));
+    }
+
+    #[test]
+    fn test_source_fingerprint_leading_zeros() {
+        let path = CryptoKeyPath::new(vec![], Some([0x00, 0x12, 0x34, 0x56]), None);
+        assert_eq!("a20180021a00123456", hex::encode(path.to_bytes()));
+        let decoded = CryptoKeyPath::from_bytes(Vec::from_hex("a2018002182a").unwrap()).unwrap();
+        assert_eq!(Some([0x00, 0x00, 0x00, 0x2a]), decoded.get_source_fingerprint());
+    }
+
+    #[test]
+    fn test_get_path_without_prefix() {
+        // get_path never renders the leading m/, whichever form the path was parsed from
+        let with_prefix = CryptoKeyPath::from_path("m/84'/0'/0'/0/0".to_string(), None).unwrap();
+        let without_prefix = CryptoKeyPath::from_path("84'/0'/0'/0/0".to_string(), None).unwrap();
+        assert_eq!(Some("84'/0'/0'/0/0".to_string()), with_prefix.get_path());
+        assert_eq!(with_prefix.get_path(), without_prefix.get_path());
+    }
+
+    #[test]
+    fn test_as_pairs() {
+        let path = CryptoKeyPath::from_path("m/48'/0'/0'/2'/1/*".to_string(), None).unwrap();
+        assert_eq!(
+            vec![
+                (Some(48), true),
+                (Some(0), true),
+                (Some(0), true),
+                (Some(2), true),
+                (Some(1), false),
+                (None, false)
+            ],
+            path.as_pairs()
+        );
+    }
+}
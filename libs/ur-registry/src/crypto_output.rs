@@ -1,22 +1,41 @@
-use serde_cbor::{from_slice, to_vec, Value};
-use crate::cbor_value::CborValue;
+use crate::cbor::{check_depth, decode, encode, Value, MAX_DEPTH};
+use crate::cbor_value::{log_decode_error, CborValue};
 use crate::crypto_hd_key::CryptoHDKey;
-use crate::registry_types::{CRYPTO_OUTPUT, RegistryType};
+use crate::registry_types::{normalize_tag, CRYPTO_OUTPUT, RegistryType};
+use crate::script_expression::ScriptExpression;
 use crate::traits::{RegistryItem, To, From};
+use crate::types::Bytes;
 
 #[derive(Clone, Debug, Default)]
 pub struct CryptoOutput {
-    //TODO: add script_expressions
+    // outermost expression first, e.g. [ScriptHash, WitnessPublicKeyHash] for sh(wpkh(...))
+    script_expressions: Vec<ScriptExpression>,
     crypto_key: CryptoHDKey, // crypto_key: CryptoHDKey, CryptoECKey, MultiKey
 }
 
 impl CryptoOutput {
     pub fn new(crypto_key: CryptoHDKey) -> Self {
         CryptoOutput {
-            crypto_key
+            script_expressions: vec![],
+            crypto_key,
         }
     }
 
+    pub fn new_with_script_expressions(script_expressions: Vec<ScriptExpression>, crypto_key: CryptoHDKey) -> Self {
+        CryptoOutput {
+            script_expressions,
+            crypto_key,
+        }
+    }
+
+    pub fn get_script_expressions(&self) -> Vec<ScriptExpression> {
+        self.script_expressions.clone()
+    }
+
+    pub fn set_script_expressions(&mut self, script_expressions: Vec<ScriptExpression>) {
+        self.script_expressions = script_expressions;
+    }
+
     pub fn get_crypto_key(&self) -> CryptoHDKey {
         self.crypto_key.clone()
     }
@@ -24,6 +43,164 @@ impl CryptoOutput {
     pub fn set_crypto_key(&mut self, crypto_key: CryptoHDKey) {
         self.crypto_key = crypto_key;
     }
+
+    pub fn is_taproot(&self) -> bool {
+        self.script_expressions.contains(&ScriptExpression::Taproot)
+    }
+
+    // taproot outputs commit to the 32 byte x-only form of the key, the xpub itself keeps the standard encoding
+    pub fn get_x_only_key(&self) -> Option<Bytes> {
+        let key = self.crypto_key.get_key();
+        match self.is_taproot() && !self.crypto_key.is_private_key() && key.len() == 33 {
+            true => Some(key[1..].to_vec()),
+            false => None,
+        }
+    }
+
+    pub fn to_descriptor_string(&self) -> String {
+        let key = &self.crypto_key;
+        let origin = key
+            .get_origin()
+            .and_then(|origin| {
+                origin
+                    .get_source_fingerprint()
+                    .map(|fingerprint| origin.to_key_origin_string(fingerprint))
+            })
+            .unwrap_or_default();
+        let children = key
+            .get_children()
+            .and_then(|children| children.get_path())
+            .map_or("".to_string(), |path| format!("/{}", path));
+        let key_expression = format!("{}{}{}", origin, key.get_bip32_key(), children);
+        self.script_expressions
+            .iter()
+            .rev()
+            .fold(key_expression, |inner, expression| {
+                format!("{}({})", expression.get_expression(), inner)
+            })
+    }
+
+    // sh only outermost, wsh and wpkh at most inside sh, the other expressions wrap the key directly
+    // and tr, combo, raw, addr and cosigner only stand alone
+    pub fn validate(&self) -> Result<(), String> {
+        if self.script_expressions.is_empty() {
+            return Err("crypto-output needs at least one script expression".to_string());
+        }
+        let last = self.script_expressions.len() - 1;
+        for (index, expression) in self.script_expressions.iter().enumerate() {
+            let parent = index.checked_sub(1).map(|index| &self.script_expressions[index]);
+            let allowed = match expression {
+                ScriptExpression::WitnessScriptHash | ScriptExpression::WitnessPublicKeyHash => {
+                    matches!(parent, None | Some(ScriptExpression::ScriptHash))
+                }
+                ScriptExpression::PublicKey
+                | ScriptExpression::PublicKeyHash
+                | ScriptExpression::MultiSig
+                | ScriptExpression::SortedMultiSig => matches!(
+                    parent,
+                    None | Some(ScriptExpression::ScriptHash) | Some(ScriptExpression::WitnessScriptHash)
+                ),
+                ScriptExpression::Unknown(tag) => {
+                    return Err(format!("unknown script expression tag {}", tag))
+                }
+                _ => parent.is_none(),
+            };
+            if let (false, Some(parent)) = (allowed, parent) {
+                return Err(format!(
+                    "{}() can't be nested in {}()",
+                    expression.get_expression(),
+                    parent.get_expression()
+                ));
+            }
+            let wraps_key = !matches!(
+                expression,
+                ScriptExpression::ScriptHash | ScriptExpression::WitnessScriptHash
+            );
+            match (wraps_key, index == last) {
+                (true, false) => {
+                    return Err(format!("{}() has to wrap the key directly", expression.get_expression()))
+                }
+                (false, true) => {
+                    return Err(format!("{}() can't wrap the key directly", expression.get_expression()))
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // the descriptor followed by `#` and its BIP-380 checksum, as wallets like Sparrow and Core expect on import
+    pub fn to_descriptor_string_with_checksum(&self) -> String {
+        let descriptor = self.to_descriptor_string();
+        let checksum = descriptor_checksum(&descriptor);
+        match checksum.is_empty() {
+            true => descriptor,
+            false => format!("{}#{}", descriptor, checksum),
+        }
+    }
+}
+
+const DESCRIPTOR_INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const DESCRIPTOR_CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn descriptor_poly_mod(c: u64, value: u64) -> u64 {
+    let c0 = c >> 35;
+    let mut c = ((c & 0x7ffffffff) << 5) ^ value;
+    [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd]
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| (c0 >> i) & 1 == 1)
+        .for_each(|(_, generator)| c ^= generator);
+    c
+}
+
+// Bitcoin Core's descriptor checksum, the part after `#`. like Core, an empty string is returned
+// when the descriptor contains a character outside the descriptor charset
+pub fn descriptor_checksum(descriptor: &str) -> String {
+    let mut c = 1;
+    let mut class = 0;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let position = match DESCRIPTOR_INPUT_CHARSET.find(ch) {
+            Some(position) => position as u64,
+            None => return String::new(),
+        };
+        c = descriptor_poly_mod(c, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = descriptor_poly_mod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = descriptor_poly_mod(c, class);
+    }
+    (0..8).for_each(|_| c = descriptor_poly_mod(c, 0));
+    c ^= 1;
+    (0..8)
+        .map(|i| DESCRIPTOR_CHECKSUM_CHARSET[((c >> (5 * (7 - i))) & 31) as usize] as char)
+        .collect()
+}
+
+// picks the script expressions from the purpose of the key origin, keys with an unknown purpose default to wpkh
+impl std::convert::From<CryptoHDKey> for CryptoOutput {
+    fn from(crypto_key: CryptoHDKey) -> Self {
+        let purpose = crypto_key
+            .get_origin()
+            .and_then(|origin| origin.get_components().first().cloned())
+            .filter(|component| component.is_hardened())
+            .and_then(|component| component.get_index());
+        let script_expressions = match purpose {
+            Some(44) => vec![ScriptExpression::PublicKeyHash],
+            Some(49) => vec![ScriptExpression::ScriptHash, ScriptExpression::WitnessPublicKeyHash],
+            Some(86) => vec![ScriptExpression::Taproot],
+            _ => vec![ScriptExpression::WitnessPublicKeyHash],
+        };
+        CryptoOutput::new_with_script_expressions(script_expressions, crypto_key)
+    }
 }
 
 impl RegistryItem for CryptoOutput {
@@ -34,31 +211,183 @@ impl RegistryItem for CryptoOutput {
 
 impl To for CryptoOutput {
     fn to_cbor(&self) -> Value {
-        let key = self.get_crypto_key().to_cbor();
-        Value::Tag(CryptoHDKey::get_registry_type().get_tag(), Box::new(key))
+        let key = self.crypto_key.to_cbor();
+        let key = Value::Tag(CryptoHDKey::get_registry_type().get_tag(), Box::new(key));
+        self.script_expressions
+            .iter()
+            .rev()
+            .fold(key, |inner, expression| {
+                Value::Tag(expression.get_tag(), Box::new(inner))
+            })
     }
 
     fn to_bytes(&self) -> Vec<u8> {
         let value = self.to_cbor();
         //TODO: remove unwrap
-        to_vec(&value).unwrap()
+        encode(&value).unwrap()
     }
 }
 
 impl From<CryptoOutput> for CryptoOutput {
     fn from_cbor(cbor: Value) -> Result<CryptoOutput, String> {
+        check_depth(&cbor, MAX_DEPTH).map_err(log_decode_error::<CryptoOutput>)?;
+        let mut script_expressions = vec![];
+        let mut cbor = cbor;
+        // every tag above the key is a script expression, unknown ones are kept as they are
+        while let Value::Tag(tag, inner) = cbor.clone() {
+            if normalize_tag(tag) == CryptoHDKey::get_registry_type().get_tag() {
+                break;
+            }
+            script_expressions.push(ScriptExpression::from_tag_or_unknown(tag));
+            cbor = *inner;
+        }
         let value = CborValue::new(cbor);
-        let tag = value.get_tag(CryptoHDKey::get_registry_type().get_tag())?;
-        let key = CryptoHDKey::from_cbor(tag.get_value().clone())?;
+        let tag = value
+            .get_tag(CryptoHDKey::get_registry_type().get_tag())
+            .map_err(log_decode_error::<CryptoOutput>)?;
+        let key = CryptoHDKey::from_cbor(tag.get_value().clone())
+            .map_err(log_decode_error::<CryptoOutput>)?;
         Ok(CryptoOutput {
-            crypto_key: key
+            script_expressions,
+            crypto_key: key,
         })
     }
     fn from_bytes(bytes: Vec<u8>) -> Result<CryptoOutput, String> {
-        let value: Value = match from_slice(bytes.as_slice()) {
-            Ok(x) => x,
-            Err(e) => return Err(e.to_string()),
-        };
+        let value = decode(bytes.as_slice())?;
         CryptoOutput::from_cbor(value)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto_hd_key::CryptoHDKey;
+    use crate::crypto_key_path::CryptoKeyPath;
+    use crate::crypto_output::{descriptor_checksum, CryptoOutput};
+    use crate::cbor::Value;
+    use crate::script_expression::ScriptExpression;
+    use crate::traits::{From, To};
+    use hex::FromHex;
+
+    #[test]
+    fn test_decode_taproot() {
+        let output = CryptoOutput::from_bytes(Vec::from_hex("d90199d9012fa5035821023da5a8b563c3a6a5bb096e6a4cca9ddf581d82749967c97195905e25b30825190458206562e8f62c9df1d41dc572f30f5d66234f8247dfef114ac39db25c4bb97abc3b06d90130a201861856f500f500f5021a3442193e07d90130a1018400f480f4081af1053104").unwrap()).unwrap();
+        assert_eq!(vec![ScriptExpression::Taproot], output.get_script_expressions());
+        assert_eq!(true, output.is_taproot());
+        assert_eq!(
+            "3da5a8b563c3a6a5bb096e6a4cca9ddf581d82749967c97195905e25b3082519",
+            hex::encode(output.get_x_only_key().unwrap())
+        );
+        assert_eq!(
+            "tr([3442193e/86'/0'/0']xpub6DRX1xNPHKaApgDnqaMNxJ8Lz35KCn3mRcW3LUep3JKhxWisRwaZJPn4BuZiaJ4kJ3cdqwbn4vZcsGiLGJJabZbqa65LGX2uhU9CtPWSgEn/0/*)",
+            output.to_descriptor_string()
+        );
+    }
+
+    #[test]
+    fn test_encode_taproot() {
+        let bytes = Vec::from_hex("d90199d9012fa4035821023da5a8b563c3a6a5bb096e6a4cca9ddf581d82749967c97195905e25b30825190458206562e8f62c9df1d41dc572f30f5d66234f8247dfef114ac39db25c4bb97abc3b06d90130a201861856f500f500f5021a3442193e081af1053104").unwrap();
+        let output = CryptoOutput::from_bytes(bytes.clone()).unwrap();
+        let output = CryptoOutput::new_with_script_expressions(
+            vec![ScriptExpression::Taproot],
+            output.get_crypto_key(),
+        );
+        assert_eq!(hex::encode(bytes), hex::encode(output.to_bytes()));
+    }
+
+    #[test]
+    fn test_from_hd_key() {
+        let expressions = |path: &str| {
+            let origin = CryptoKeyPath::from_path(path.to_string(), Some([0x34, 0x42, 0x19, 0x3e])).unwrap();
+            let key = CryptoHDKey::new_mainnet_extended(vec![0x02; 33], vec![0; 32], Some(origin));
+            CryptoOutput::from(key).get_script_expressions()
+        };
+        assert_eq!(vec![ScriptExpression::PublicKeyHash], expressions("m/44'/0'/0'"));
+        assert_eq!(
+            vec![ScriptExpression::ScriptHash, ScriptExpression::WitnessPublicKeyHash],
+            expressions("m/49'/0'/0'")
+        );
+        assert_eq!(vec![ScriptExpression::WitnessPublicKeyHash], expressions("m/84'/0'/0'"));
+        assert_eq!(vec![ScriptExpression::Taproot], expressions("m/86'/0'/0'"));
+        assert_eq!(vec![ScriptExpression::WitnessPublicKeyHash], expressions("m/45'/0'/0'"));
+    }
+
+    #[test]
+    fn test_decode_cosigner() {
+        let bytes = Vec::from_hex("d9019ad9012fa4035821023da5a8b563c3a6a5bb096e6a4cca9ddf581d82749967c97195905e25b30825190458206562e8f62c9df1d41dc572f30f5d66234f8247dfef114ac39db25c4bb97abc3b06d90130a201861856f500f500f5021a3442193e081af1053104").unwrap();
+        let output = CryptoOutput::from_bytes(bytes.clone()).unwrap();
+        assert_eq!(vec![ScriptExpression::Cosigner], output.get_script_expressions());
+        assert_eq!(false, output.is_taproot());
+        assert_eq!(
+            "cosigner([3442193e/86'/0'/0']xpub6DRX1xNPHKaApgDnqaMNxJ8Lz35KCn3mRcW3LUep3JKhxWisRwaZJPn4BuZiaJ4kJ3cdqwbn4vZcsGiLGJJabZbqa65LGX2uhU9CtPWSgEn)",
+            output.to_descriptor_string()
+        );
+        assert_eq!(hex::encode(bytes), hex::encode(output.to_bytes()));
+    }
+
+    #[test]
+    fn test_descriptor_checksum() {
+        assert_eq!("89f8spxm", descriptor_checksum("raw(deadbeef)"));
+        assert_eq!(
+            "ml40v0wf",
+            descriptor_checksum("pkh([d34db33f/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*)")
+        );
+        assert_eq!("", descriptor_checksum("raw(deadbeef)\u{e9}"));
+    }
+
+    #[test]
+    fn test_decode_over_nested() {
+        // 40 sh() tags around an hdkey
+        let key = "d9012fa203582102eae4b876a8696134b868f88cc2f51f715f2dbedb7446b8e6edf3d4541c4eb67b06d90130a10180";
+        let bytes = Vec::from_hex(format!("{}{}", "d90190".repeat(40), key)).unwrap();
+        assert_eq!(
+            "cbor nesting exceeds the maximum depth",
+            CryptoOutput::from_bytes(bytes).unwrap_err()
+        );
+        let value = (0..40).fold(Value::Integer(0), |inner, _| Value::Tag(400, Box::new(inner)));
+        assert!(CryptoOutput::from_cbor(value).is_err());
+        assert!(CryptoOutput::from_bytes(Vec::from_hex(format!("d90190{}", key)).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_validate() {
+        let key = CryptoHDKey::new_mainnet_extended(vec![0x02; 33], vec![0; 32], None);
+        let output = |expressions: Vec<ScriptExpression>| {
+            CryptoOutput::new_with_script_expressions(expressions, key.clone()).validate()
+        };
+        assert!(output(vec![ScriptExpression::ScriptHash, ScriptExpression::WitnessPublicKeyHash]).is_ok());
+        assert!(output(vec![
+            ScriptExpression::ScriptHash,
+            ScriptExpression::WitnessScriptHash,
+            ScriptExpression::SortedMultiSig
+        ])
+        .is_ok());
+        assert!(output(vec![ScriptExpression::Taproot]).is_ok());
+        assert_eq!(
+            Err("wpkh() has to wrap the key directly".to_string()),
+            output(vec![ScriptExpression::WitnessPublicKeyHash, ScriptExpression::ScriptHash])
+        );
+        assert_eq!(
+            Err("tr() can't be nested in sh()".to_string()),
+            output(vec![ScriptExpression::ScriptHash, ScriptExpression::Taproot])
+        );
+        assert_eq!(
+            Err("wsh() can't wrap the key directly".to_string()),
+            output(vec![ScriptExpression::WitnessScriptHash])
+        );
+        assert!(output(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_unknown_script_expression() {
+        // sh(<499>(hdkey))
+        let key = "d9012fa203582102eae4b876a8696134b868f88cc2f51f715f2dbedb7446b8e6edf3d4541c4eb67b06d90130a10180";
+        let bytes = Vec::from_hex(format!("d90190d901f3{}", key)).unwrap();
+        let output = CryptoOutput::from_bytes(bytes.clone()).unwrap();
+        assert_eq!(
+            vec![ScriptExpression::ScriptHash, ScriptExpression::Unknown(499)],
+            output.get_script_expressions()
+        );
+        assert_eq!(hex::encode(bytes), hex::encode(output.to_bytes()));
+        assert_eq!(Err("unknown script expression tag 499".to_string()), output.validate());
+    }
+}
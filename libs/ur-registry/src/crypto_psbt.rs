@@ -1,12 +1,19 @@
-use serde_cbor::{from_slice, to_vec, Value};
+use crate::cbor::{decode, encode, Value};
+use sha2::{Digest, Sha256};
 
 use crate::{
-    cbor_value::CborValue,
+    cbor_value::{log_decode_error, log_field_error, CborValue},
     registry_types::{RegistryType, CRYPTO_PSBT},
     traits::{From, RegistryItem, To},
-    types::Bytes,
+    types::{Bytes, Fingerprint},
 };
 
+const PSBT: i128 = 1;
+
+const PSBT_MAGIC: [u8; 5] = [0x70, 0x73, 0x62, 0x74, 0xff];
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const PSBT_IN_BIP32_DERIVATION: u8 = 0x06;
+
 #[derive(Debug, Clone, Default)]
 pub struct CryptoPSBT {
     psbt: Bytes,
@@ -24,6 +31,83 @@ impl CryptoPSBT {
     pub fn set_psbt(&mut self, psbt: Bytes) {
         self.psbt = psbt;
     }
+
+    // txid of the global unsigned transaction, in the byte order used for display
+    pub fn unsigned_txid(&self) -> Result<[u8; 32], String> {
+        let (unsigned_tx, _) = read_unsigned_tx(self.psbt.as_slice())?;
+        let mut txid: [u8; 32] = Sha256::digest(Sha256::digest(unsigned_tx)).into();
+        txid.reverse();
+        Ok(txid)
+    }
+}
+
+// checks whether any input carries a bip32 derivation from the given master fingerprint
+pub fn psbt_uses_fingerprint(psbt: &[u8], xfp: Fingerprint) -> Result<bool, String> {
+    let (unsigned_tx, mut offset) = read_unsigned_tx(psbt)?;
+    // the input count follows the 4 byte version, unsigned transactions carry no segwit marker
+    let mut tx_offset = 4;
+    let input_count = read_compact_size(unsigned_tx, &mut tx_offset)?;
+    for _ in 0..input_count {
+        let uses_fingerprint = read_map(psbt, &mut offset)?.iter().any(|(key, value)| {
+            key.first() == Some(&PSBT_IN_BIP32_DERIVATION) && value.starts_with(&xfp)
+        });
+        if uses_fingerprint {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// returns the global unsigned transaction and the offset of the first input map
+fn read_unsigned_tx(psbt: &[u8]) -> Result<(&[u8], usize), String> {
+    if !psbt.starts_with(&PSBT_MAGIC) {
+        return Err("invalid psbt magic bytes".to_string());
+    }
+    let mut offset = PSBT_MAGIC.len();
+    let unsigned_tx = read_map(psbt, &mut offset)?
+        .into_iter()
+        .find(|(key, _)| *key == [PSBT_GLOBAL_UNSIGNED_TX])
+        .map(|(_, value)| value)
+        .ok_or("psbt does not contain an unsigned transaction".to_string())?;
+    Ok((unsigned_tx, offset))
+}
+
+fn read_map<'a>(psbt: &'a [u8], offset: &mut usize) -> Result<Vec<(&'a [u8], &'a [u8])>, String> {
+    let mut entries = vec![];
+    loop {
+        let key_length = read_compact_size(psbt, offset)? as usize;
+        if key_length == 0 {
+            return Ok(entries);
+        }
+        let key = read_slice(psbt, offset, key_length)?;
+        let value_length = read_compact_size(psbt, offset)? as usize;
+        let value = read_slice(psbt, offset, value_length)?;
+        entries.push((key, value));
+    }
+}
+
+fn read_slice<'a>(bytes: &'a [u8], offset: &mut usize, length: usize) -> Result<&'a [u8], String> {
+    let end = offset
+        .checked_add(length)
+        .filter(|end| *end <= bytes.len())
+        .ok_or("unexpected end of psbt".to_string())?;
+    let slice = &bytes[*offset..end];
+    *offset = end;
+    Ok(slice)
+}
+
+fn read_compact_size(bytes: &[u8], offset: &mut usize) -> Result<u64, String> {
+    let prefix = read_slice(bytes, offset, 1)?[0];
+    let length = match prefix {
+        0xfd => 2,
+        0xfe => 4,
+        0xff => 8,
+        x => return Ok(x as u64),
+    };
+    Ok(read_slice(bytes, offset, length)?
+        .iter()
+        .rev()
+        .fold(0u64, |acc, byte| (acc << 8) | *byte as u64))
 }
 
 impl RegistryItem for CryptoPSBT {
@@ -40,22 +124,65 @@ impl To for CryptoPSBT {
     fn to_bytes(&self) -> Vec<u8> {
         let value = self.to_cbor();
         //TODO: remove unwrap
-        to_vec(&value).unwrap()
+        encode(&value).unwrap()
     }
 }
 
 impl From<CryptoPSBT> for CryptoPSBT {
+    // the spec uses bare bytes, some producers wrap them as {1: bytes}
     fn from_cbor(cbor: Value) -> Result<CryptoPSBT, String> {
-        let value = CborValue::new(cbor);
-        let bytes = value.get_bytes()?;
+        let value = match cbor {
+            Value::Map(map) if map.len() == 1 => map
+                .into_iter()
+                .find(|(key, _)| *key == Value::Integer(PSBT))
+                .map(|(_, value)| value)
+                .ok_or("unexpected key in crypto-psbt map, expected 1".to_string())
+                .map_err(log_field_error::<CryptoPSBT>(PSBT))?,
+            x => x,
+        };
+        let bytes = CborValue::new(value).get_bytes().map_err(log_decode_error::<CryptoPSBT>)?;
         Ok(CryptoPSBT { psbt: bytes })
     }
 
     fn from_bytes(bytes: Vec<u8>) -> Result<CryptoPSBT, String> {
-        let value: Value = match from_slice(bytes.as_slice()) {
-            Ok(x) => x,
-            Err(e) => return Err(e.to_string()),
-        };
+        let value = decode(bytes.as_slice())?;
         CryptoPSBT::from_cbor(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto_psbt::{psbt_uses_fingerprint, CryptoPSBT};
+    use crate::traits::{From, To};
+    use hex::FromHex;
+
+    #[test]
+    fn test_unsigned_txid() {
+        let psbt = CryptoPSBT::new(Vec::from_hex("70736274ff0100750200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf60000000000feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e130000000000").unwrap());
+        assert_eq!(
+            "af2cac1e0e33d896d9d0751d66fcb2fa54b737c7a13199281fb57e4f497bb652",
+            hex::encode(psbt.unsigned_txid().unwrap())
+        );
+        assert!(CryptoPSBT::new(vec![0x70, 0x73, 0x62, 0x74, 0xff, 0x00]).unsigned_txid().is_err());
+        assert!(CryptoPSBT::new(vec![0x70, 0x73, 0x62, 0x74, 0xff, 0x01, 0x00, 0x75]).unsigned_txid().is_err());
+    }
+
+    #[test]
+    fn test_psbt_uses_fingerprint() {
+        let psbt = Vec::from_hex("70736274ff0100750200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf60000000000feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e1300002206029583bf39ae0a609747ad199addd634fa6108559d6c5cd39b4c2183f1ab96e07f10d90c6a4f540000800000008000000080000000").unwrap();
+        assert_eq!(true, psbt_uses_fingerprint(&psbt, [0xd9, 0x0c, 0x6a, 0x4f]).unwrap());
+        assert_eq!(false, psbt_uses_fingerprint(&psbt, [0x73, 0xc5, 0xda, 0x0a]).unwrap());
+        assert!(psbt_uses_fingerprint(&psbt[..psbt.len() - 40], [0xd9, 0x0c, 0x6a, 0x4f]).is_err());
+    }
+
+    #[test]
+    fn test_decode_wrapped_psbt() {
+        let bare = CryptoPSBT::from_bytes(Vec::from_hex("4570736274ff").unwrap()).unwrap();
+        assert_eq!("70736274ff", hex::encode(bare.get_psbt()));
+        let wrapped = CryptoPSBT::from_bytes(Vec::from_hex("a1014570736274ff").unwrap()).unwrap();
+        assert_eq!("70736274ff", hex::encode(wrapped.get_psbt()));
+        assert_eq!("4570736274ff", hex::encode(wrapped.to_bytes()));
+        assert!(CryptoPSBT::from_bytes(Vec::from_hex("a1024570736274ff").unwrap()).is_err());
+        assert!(CryptoPSBT::from_bytes(Vec::from_hex("a2014570736274ff0201").unwrap()).is_err());
+    }
+}
@@ -1,9 +1,10 @@
-use crate::cbor_value::CborValue;
+use crate::cbor_value::{log_decode_error, log_field_error, CborValue};
 use crate::crypto_key_path::CryptoKeyPath;
+use crate::ethereum::eth_transaction::EthTransaction;
 use crate::registry_types::{RegistryType, CRYPTO_KEYPATH, UUID, ETH_SIGN_REQUEST};
-use crate::traits::{From, RegistryItem, To};
+use crate::traits::{From, RegistryItem, RequestOrigin, To};
 use crate::types::Bytes;
-use serde_cbor::{from_slice, to_vec, Value};
+use crate::cbor::{decode, encode, Value};
 use std::collections::BTreeMap;
 
 const REQUEST_ID: i128 = 1;
@@ -14,7 +15,7 @@ const DERIVATION_PATH: i128 = 5;
 const ADDRESS: i128 = 6;
 const ORIGIN: i128 = 7;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum DataType {
     Transaction = 1,
     TypedData = 2,
@@ -30,6 +31,10 @@ impl Default for DataType {
 
 impl DataType {
     pub fn from_u32(i: u32) -> Result<Self, String> {
+        DataType::from_i128(i as i128)
+    }
+
+    pub fn from_i128(i: i128) -> Result<Self, String> {
         match i {
             1 => Ok(DataType::Transaction),
             2 => Ok(DataType::TypedData),
@@ -41,6 +46,10 @@ impl DataType {
             )),
         }
     }
+
+    pub fn as_i128(&self) -> i128 {
+        self.clone() as i128
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -123,6 +132,31 @@ impl EthSignRequest {
     pub fn get_origin(&self) -> Option<String> {
         self.origin.clone()
     }
+
+    pub fn decode_legacy_tx(&self) -> Result<EthTransaction, String> {
+        match self.data_type {
+            DataType::Transaction => {}
+            _ => return Err("sign data is not a legacy transaction".to_string()),
+        }
+        let chain_id = self
+            .chain_id
+            .map(|x| u64::try_from(x).map_err(|_| format!("invalid chain id {}", x)))
+            .transpose()?;
+        EthTransaction::decode_legacy(&self.sign_data, chain_id)
+    }
+
+    pub fn decode_eip1559_tx(&self) -> Result<EthTransaction, String> {
+        match self.data_type {
+            DataType::TypedTransaction => EthTransaction::decode_eip1559(&self.sign_data),
+            _ => Err("sign data is not a typed transaction".to_string()),
+        }
+    }
+}
+
+impl RequestOrigin for EthSignRequest {
+    fn get_origin(&self) -> Option<String> {
+        self.origin.clone()
+    }
 }
 
 impl RegistryItem for EthSignRequest {
@@ -146,7 +180,7 @@ impl To for EthSignRequest {
         );
         map.insert(
             Value::Integer(DATA_TYPE),
-            Value::Integer(self.get_data_type() as i128),
+            Value::Integer(self.get_data_type().as_i128()),
         );
         self.get_chain_id().and_then(|chain_id| map.insert(Value::Integer(CHAIN_ID), Value::Integer(chain_id)));
         map.insert(
@@ -165,50 +199,55 @@ impl To for EthSignRequest {
 
     fn to_bytes(&self) -> Vec<u8> {
         let value = self.to_cbor();
-        to_vec(&value).unwrap()
+        encode(&value).unwrap()
     }
 }
 
 impl From<EthSignRequest> for EthSignRequest {
     fn from_cbor(cbor: Value) -> Result<EthSignRequest, String> {
         let value = CborValue::new(cbor);
-        let map = value.get_map()?;
+        let map = value.get_map().map_err(log_decode_error::<EthSignRequest>)?;
         let request_id = map
             .get_by_integer(REQUEST_ID)
             .map(|v| v.get_tag(UUID.get_tag()).and_then(|v| v.get_bytes()))
-            .transpose()?;
-        let sign_data = map.get_by_integer(SIGN_DATA).map_or(
-            Err("sign_data is required for sol-sign-request".to_string()),
-            |v| v.get_bytes(),
-        )?;
+            .transpose()
+            .map_err(log_field_error::<EthSignRequest>(REQUEST_ID))?;
+        let sign_data = map
+            .get_by_integer(SIGN_DATA)
+            .map_or(
+                Err("sign_data is required for sol-sign-request".to_string()),
+                |v| v.get_bytes(),
+            )
+            .map_err(log_field_error::<EthSignRequest>(SIGN_DATA))?;
         let data_type = map.get_by_integer(DATA_TYPE)
             .map_or(Err("data_type is required for eth-sign-request".to_string()), |v| v.get_integer())
-            .and_then(|v| match v {
-                1 => Ok(DataType::Transaction),
-                2 => Ok(DataType::TypedData),
-                3 => Ok(DataType::PersonalMessage),
-                4 => Ok(DataType::TypedTransaction),
-                x => Err(format!(
-                    "invalid value for data_type in eth-sign-request, expected (1, 2, 3, 4), received {:?}",
-                    x
-                )),
-            })?;
-        let chain_id = map.get_by_integer(CHAIN_ID).map(|v| v.get_integer()).transpose()?;
-        let derivation_path = map.get_by_integer(DERIVATION_PATH).map_or(
-            Err("derivation_path is required for sol-sign-request".to_string()),
-            |v| {
-                v.get_tag(CRYPTO_KEYPATH.get_tag())
-                    .and_then(|v| CryptoKeyPath::from_cbor(v.get_value().clone()))
-            },
-        )?;
+            .and_then(DataType::from_i128)
+            .map_err(log_field_error::<EthSignRequest>(DATA_TYPE))?;
+        let chain_id = map
+            .get_by_integer(CHAIN_ID)
+            .map(|v| v.get_integer())
+            .transpose()
+            .map_err(log_field_error::<EthSignRequest>(CHAIN_ID))?;
+        let derivation_path = map
+            .get_by_integer(DERIVATION_PATH)
+            .map_or(
+                Err("derivation_path is required for sol-sign-request".to_string()),
+                |v| {
+                    v.get_tag(CRYPTO_KEYPATH.get_tag())
+                        .and_then(|v| CryptoKeyPath::from_cbor(v.get_value().clone()))
+                },
+            )
+            .map_err(log_field_error::<EthSignRequest>(DERIVATION_PATH))?;
         let address = map
             .get_by_integer(ADDRESS)
             .map(|v| v.get_bytes())
-            .transpose()?;
+            .transpose()
+            .map_err(log_field_error::<EthSignRequest>(ADDRESS))?;
         let origin = map
             .get_by_integer(ORIGIN)
             .map(|v| v.get_text())
-            .transpose()?;
+            .transpose()
+            .map_err(log_field_error::<EthSignRequest>(ORIGIN))?;
 
         Ok(EthSignRequest {
             request_id,
@@ -222,10 +261,107 @@ impl From<EthSignRequest> for EthSignRequest {
     }
 
     fn from_bytes(bytes: Vec<u8>) -> Result<EthSignRequest, String> {
-        let value: Value = match from_slice(bytes.as_slice()) {
-            Ok(x) => x,
-            Err(e) => return Err(e.to_string()),
-        };
+        let value = decode(bytes.as_slice())?;
         EthSignRequest::from_cbor(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto_key_path::CryptoKeyPath;
+    use crate::ethereum::eth_sign_request::{DataType, EthSignRequest};
+    use crate::traits::{From, RequestOrigin, To};
+    use hex::FromHex;
+
+    #[test]
+    fn test_origin() {
+        let request = EthSignRequest::new(
+            None,
+            Vec::from_hex("48656c6c6f").unwrap(),
+            DataType::PersonalMessage,
+            Some(1),
+            CryptoKeyPath::from_path("m/44'/60'/0'/0/0".to_string(), Some([0xf2, 0x3f, 0x9f, 0xd2]))
+                .unwrap(),
+            None,
+            Some("MetaMask".to_string()),
+        );
+        let bytes = request.to_bytes();
+        assert_eq!(
+            "a5024548656c6c6f0303040105d90130a2018a182cf5183cf500f500f400f4021af23f9fd207684d6574614d61736b",
+            hex::encode(&bytes)
+        );
+        let request = EthSignRequest::from_bytes(bytes).unwrap();
+        assert_eq!(Some("MetaMask".to_string()), RequestOrigin::get_origin(&request));
+    }
+
+    #[test]
+    fn test_decode_legacy_tx() {
+        let request = EthSignRequest::new(
+            None,
+            Vec::from_hex("ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080").unwrap(),
+            DataType::Transaction,
+            Some(1),
+            CryptoKeyPath::from_path("m/44'/60'/0'/0/0".to_string(), None).unwrap(),
+            None,
+            None,
+        );
+        let tx = request.decode_legacy_tx().unwrap();
+        assert_eq!(9, tx.get_nonce());
+        assert_eq!(21000, tx.get_gas_limit());
+        assert_eq!(Some([0x35; 20]), tx.get_to());
+        assert_eq!(1_000_000_000_000_000_000, tx.get_value());
+        assert_eq!(Vec::<u8>::new(), tx.get_data());
+        assert_eq!(1, tx.get_chain_id());
+        assert!(request.decode_eip1559_tx().is_err());
+
+        let mut truncated = request.clone();
+        truncated.set_sign_data(Vec::from_hex("ec098504a817c800825208943535").unwrap());
+        assert!(truncated.decode_legacy_tx().is_err());
+    }
+
+    #[test]
+    fn test_decode_eip1559_tx() {
+        let request = EthSignRequest::new(
+            None,
+            Vec::from_hex("02f86d0142847735940085174876e80082ea6094a0b86991c6218b36c1d19d4a2e9eb0ce3606eb4880b844a9059cbb0000000000000000000000003535353535353535353535353535353535353535000000000000000000000000000000000000000000000000000000003b9aca00c0").unwrap(),
+            DataType::TypedTransaction,
+            Some(1),
+            CryptoKeyPath::from_path("m/44'/60'/0'/0/0".to_string(), None).unwrap(),
+            None,
+            None,
+        );
+        let tx = request.decode_eip1559_tx().unwrap();
+        assert_eq!(1, tx.get_chain_id());
+        assert_eq!(0x42, tx.get_nonce());
+        assert_eq!(60000, tx.get_gas_limit());
+        assert_eq!(
+            "a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+            hex::encode(tx.get_to().unwrap())
+        );
+        assert_eq!(0, tx.get_value());
+        assert_eq!("a9059cbb", hex::encode(&tx.get_data()[..4]));
+        assert!(request.decode_legacy_tx().is_err());
+    }
+
+    #[test]
+    fn test_data_type() {
+        let data_types = [
+            (1, DataType::Transaction),
+            (2, DataType::TypedData),
+            (3, DataType::PersonalMessage),
+            (4, DataType::TypedTransaction),
+        ];
+        for (value, data_type) in data_types {
+            assert_eq!(data_type, DataType::from_i128(value).unwrap());
+            assert_eq!(value, data_type.as_i128());
+        }
+        assert!(DataType::from_i128(0).is_err());
+        assert!(DataType::from_i128(-1).is_err());
+
+        // {2: h'48656c6c6f', 3: 5, 5: 304({1: [44, true, 60, true, 0, true, 0, false, 0, false]})}
+        assert_eq!(
+            "invalid value for data_type in eth-sign-request, expected (1, 2, 3, 4), received 5",
+            EthSignRequest::from_bytes(Vec::from_hex("a3024548656c6c6f030505d90130a1018a182cf5183cf500f500f400f4").unwrap()).unwrap_err()
+        );
+    }
+}
@@ -1,8 +1,14 @@
-use crate::cbor_value::CborValue;
+use crate::cbor_value::{log_decode_error, log_field_error, CborValue};
 use crate::registry_types::{ETH_SIGNATURE, RegistryType, UUID};
 use crate::traits::{From, RegistryItem, To};
 use crate::types::{Bytes, CborMap};
-use serde_cbor::{from_slice, to_vec, Value};
+use crate::cbor::{decode, encode, Value};
+#[cfg(all(feature = "eth", feature = "secp256k1"))]
+use crate::crypto::secp::recover_public_key;
+#[cfg(all(feature = "eth", feature = "secp256k1"))]
+use crate::ethereum::eth_sign_request::{DataType, EthSignRequest};
+#[cfg(all(feature = "eth", feature = "secp256k1"))]
+use crate::ethereum::hash::keccak256;
 
 const REQUEST_ID: i128 = 1;
 const SIGNATURE: i128 = 2;
@@ -41,6 +47,61 @@ impl EthSignature {
         self.signature.clone()
     }
     pub fn get_origin(&self) -> Option<String> { self.origin.clone() }
+
+    pub fn to_hex_string(&self) -> String {
+        format!("0x{}", hex::encode(&self.signature))
+    }
+
+    // the recovery id from v, which may be 0/1, 27/28 or EIP-155 encoded. returned as 27/28 when
+    // `add_27` is set and as 0/1 otherwise
+    pub fn v_normalized(&self, add_27: bool) -> Result<u8, String> {
+        if self.signature.len() < 65 {
+            return Err(format!("invalid eth signature length: {}", self.signature.len()));
+        }
+        let v = self.signature[64..]
+            .iter()
+            .fold(0u64, |acc, byte| (acc << 8) | *byte as u64);
+        let recovery_id = match v {
+            0 | 1 => v,
+            27 | 28 => v - 27,
+            // EIP-155: v = chain_id * 2 + 35 + recovery id
+            x if x >= 35 => (x - 35) % 2,
+            x => return Err(format!("invalid recovery value in eth signature: {}", x)),
+        };
+        match add_27 {
+            true => Ok(recovery_id as u8 + 27),
+            false => Ok(recovery_id as u8),
+        }
+    }
+
+    // recovers the signer from r || s || v and checks it against the address of the request when present
+    #[cfg(all(feature = "eth", feature = "secp256k1"))]
+    pub fn recover_address(&self, sign_request: &EthSignRequest) -> Result<String, String> {
+        let signature = self.get_signature();
+        let recovery_id = self.v_normalized(false)?;
+        let sign_data = sign_request.get_sign_data();
+        let message_hash = match sign_request.get_data_type() {
+            DataType::Transaction | DataType::TypedTransaction => keccak256(&sign_data),
+            DataType::PersonalMessage => {
+                let mut message = format!("\x19Ethereum Signed Message:\n{}", sign_data.len()).into_bytes();
+                message.extend(sign_data);
+                keccak256(&message)
+            }
+            DataType::TypedData => {
+                return Err("address recovery is not supported for typed data".to_string())
+            }
+        };
+        let public_key = recover_public_key(&message_hash, &signature[..64], recovery_id as i32)?;
+        let address = keccak256(&public_key.serialize_uncompressed()[1..])[12..].to_vec();
+        match sign_request.get_address() {
+            Some(expected) if expected != address => Err(format!(
+                "recovered address 0x{} does not match the requested address 0x{}",
+                hex::encode(address),
+                hex::encode(expected)
+            )),
+            _ => Ok(format!("0x{}", hex::encode(address))),
+        }
+    }
 }
 
 impl RegistryItem for EthSignature {
@@ -70,26 +131,31 @@ impl To for EthSignature {
 
     fn to_bytes(&self) -> Vec<u8> {
         let value = self.to_cbor();
-        to_vec(&value).unwrap()
+        encode(&value).unwrap()
     }
 }
 
 impl From<EthSignature> for EthSignature {
     fn from_cbor(cbor: Value) -> Result<EthSignature, String> {
         let cbor_value = CborValue::new(cbor);
-        let map = cbor_value.get_map()?;
+        let map = cbor_value.get_map().map_err(log_decode_error::<EthSignature>)?;
         let request_id = map
             .get_by_integer(REQUEST_ID)
             .map(|v| v.get_tag(UUID.get_tag()).and_then(|v| v.get_bytes()))
-            .transpose()?;
-        let signature = map.get_by_integer(SIGNATURE).map_or(
-            Err("signature is required for sol-signature".to_string()),
-            |r| r.get_bytes(),
-        )?;
+            .transpose()
+            .map_err(log_field_error::<EthSignature>(REQUEST_ID))?;
+        let signature = map
+            .get_by_integer(SIGNATURE)
+            .map_or(
+                Err("signature is required for sol-signature".to_string()),
+                |r| r.get_bytes(),
+            )
+            .map_err(log_field_error::<EthSignature>(SIGNATURE))?;
         let origin = map
             .get_by_integer(ORIGIN)
             .map(|v| v.get_text())
-            .transpose()?;
+            .transpose()
+            .map_err(log_field_error::<EthSignature>(ORIGIN))?;
         Ok(EthSignature {
             request_id,
             signature,
@@ -98,10 +164,62 @@ impl From<EthSignature> for EthSignature {
     }
 
     fn from_bytes(bytes: Vec<u8>) -> Result<EthSignature, String> {
-        let value: Value = match from_slice(bytes.as_slice()) {
-            Ok(x) => x,
-            Err(e) => return Err(e.to_string()),
-        };
+        let value = decode(bytes.as_slice())?;
         EthSignature::from_cbor(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_v_normalized() {
+        use crate::ethereum::eth_signature::EthSignature;
+        use hex::FromHex;
+
+        let rs = "28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa63627667cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+        let signature = |v: &str| EthSignature::new(None, Vec::from_hex(format!("{}{}", rs, v)).unwrap(), None);
+        assert_eq!(format!("0x{}1b", rs), signature("1b").to_hex_string());
+        assert_eq!(132, signature("1b").to_hex_string().len());
+        assert_eq!(Ok(27), signature("1b").v_normalized(true));
+        assert_eq!(Ok(0), signature("1b").v_normalized(false));
+        assert_eq!(Ok(28), signature("01").v_normalized(true));
+        assert_eq!(Ok(1), signature("01").v_normalized(false));
+        // EIP-155 on mainnet, 37 = 1 * 2 + 35 + 0
+        assert_eq!(Ok(27), signature("25").v_normalized(true));
+        assert_eq!(Ok(0), signature("25").v_normalized(false));
+        assert!(signature("02").v_normalized(true).is_err());
+        assert!(EthSignature::new(None, vec![0; 64], None).v_normalized(false).is_err());
+    }
+
+    #[cfg(all(feature = "eth", feature = "secp256k1"))]
+    #[test]
+    fn test_recover_address() {
+        use crate::crypto_key_path::CryptoKeyPath;
+        use crate::ethereum::eth_sign_request::{DataType, EthSignRequest};
+        use crate::ethereum::eth_signature::EthSignature;
+        use hex::FromHex;
+
+        // EIP-155 example transaction signed with the private key 0x4646...46
+        let mut sign_request = EthSignRequest::new(
+            None,
+            Vec::from_hex("ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080").unwrap(),
+            DataType::Transaction,
+            Some(1),
+            CryptoKeyPath::from_path("m/44'/60'/0'/0/0".to_string(), None).unwrap(),
+            Some(Vec::from_hex("9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f").unwrap()),
+            None,
+        );
+        let signature = EthSignature::new(
+            None,
+            Vec::from_hex("28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa63627667cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d8325").unwrap(),
+            None,
+        );
+        assert_eq!(
+            "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f",
+            signature.recover_address(&sign_request).unwrap()
+        );
+
+        sign_request.set_address(Vec::from_hex("3535353535353535353535353535353535353535").unwrap());
+        assert!(signature.recover_address(&sign_request).is_err());
+    }
+}
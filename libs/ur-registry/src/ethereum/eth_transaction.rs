@@ -0,0 +1,100 @@
+use crate::ethereum::rlp::{decode, decode_uint, RlpItem};
+
+const EIP1559_TX_TYPE: u8 = 0x02;
+
+// the fields needed to show a transaction for review, gas prices are not included
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EthTransaction {
+    to: Option<[u8; 20]>,
+    value: u128,
+    gas_limit: u128,
+    nonce: u64,
+    data: Vec<u8>,
+    chain_id: u64,
+}
+
+impl EthTransaction {
+    pub fn get_to(&self) -> Option<[u8; 20]> {
+        self.to
+    }
+    pub fn get_value(&self) -> u128 {
+        self.value
+    }
+    pub fn get_gas_limit(&self) -> u128 {
+        self.gas_limit
+    }
+    pub fn get_nonce(&self) -> u64 {
+        self.nonce
+    }
+    pub fn get_data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+    pub fn get_chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    // [nonce, gas_price, gas_limit, to, value, data] with [chain_id, 0, 0] appended under EIP-155,
+    // pre EIP-155 transactions take the chain id from the request
+    pub fn decode_legacy(sign_data: &[u8], chain_id: Option<u64>) -> Result<Self, String> {
+        let item = decode(sign_data)?;
+        let fields = item.get_list()?;
+        let chain_id = match fields.len() {
+            6 => chain_id.ok_or("chain id is required for a pre EIP-155 transaction".to_string())?,
+            9 => decode_u64(&fields[6])?,
+            x => {
+                return Err(format!(
+                    "invalid legacy transaction, expected 6 or 9 fields, received {}",
+                    x
+                ))
+            }
+        };
+        Ok(EthTransaction {
+            nonce: decode_u64(&fields[0])?,
+            gas_limit: decode_uint(&fields[2], 16)?,
+            to: decode_to(&fields[3])?,
+            value: decode_uint(&fields[4], 16)?,
+            data: fields[5].get_bytes()?.to_vec(),
+            chain_id,
+        })
+    }
+
+    // 0x02 || [chain_id, nonce, max_priority_fee_per_gas, max_fee_per_gas, gas_limit, to, value, data, access_list]
+    pub fn decode_eip1559(sign_data: &[u8]) -> Result<Self, String> {
+        let payload = match sign_data.split_first() {
+            Some((&EIP1559_TX_TYPE, payload)) => payload,
+            _ => return Err("invalid EIP-1559 transaction, expected type 0x02".to_string()),
+        };
+        let item = decode(payload)?;
+        let fields = item.get_list()?;
+        if fields.len() != 9 {
+            return Err(format!(
+                "invalid EIP-1559 transaction, expected 9 fields, received {}",
+                fields.len()
+            ));
+        }
+        fields[8].get_list()?;
+        Ok(EthTransaction {
+            chain_id: decode_u64(&fields[0])?,
+            nonce: decode_u64(&fields[1])?,
+            gas_limit: decode_uint(&fields[4], 16)?,
+            to: decode_to(&fields[5])?,
+            value: decode_uint(&fields[6], 16)?,
+            data: fields[7].get_bytes()?.to_vec(),
+        })
+    }
+}
+
+fn decode_u64(item: &RlpItem) -> Result<u64, String> {
+    decode_uint(item, 8).map(|x| x as u64)
+}
+
+// an empty recipient is a contract creation
+fn decode_to(item: &RlpItem) -> Result<Option<[u8; 20]>, String> {
+    match item.get_bytes()? {
+        [] => Ok(None),
+        x => x
+            .try_into()
+            .map(Some)
+            .map_err(|_| format!("invalid recipient length {}, expected 20 bytes", x.len())),
+    }
+}
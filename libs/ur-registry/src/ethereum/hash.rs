@@ -0,0 +1,22 @@
+use tiny_keccak::{Hasher, Keccak};
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    let mut output = [0u8; 32];
+    hasher.update(data);
+    hasher.finalize(&mut output);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::hash::keccak256;
+
+    #[test]
+    fn test_keccak256_empty() {
+        assert_eq!(
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+            hex::encode(keccak256(&[]))
+        );
+    }
+}
@@ -1,2 +1,6 @@
 pub mod eth_sign_request;
-pub mod eth_signature;
\ No newline at end of file
+pub mod eth_signature;
+pub mod eth_transaction;
+#[cfg(feature = "eth")]
+pub mod hash;
+pub mod rlp;
@@ -0,0 +1,178 @@
+#[derive(Clone, Debug, PartialEq)]
+pub enum RlpItem {
+    Bytes(Vec<u8>),
+    List(Vec<RlpItem>),
+}
+
+impl RlpItem {
+    pub fn get_bytes(&self) -> Result<&[u8], String> {
+        match self {
+            RlpItem::Bytes(x) => Ok(x),
+            RlpItem::List(_) => Err("expected rlp bytes, found a list".to_string()),
+        }
+    }
+
+    pub fn get_list(&self) -> Result<&[RlpItem], String> {
+        match self {
+            RlpItem::List(x) => Ok(x),
+            RlpItem::Bytes(_) => Err("expected rlp list, found bytes".to_string()),
+        }
+    }
+}
+
+// a transaction nests its access list three levels deep, this leaves plenty of room while keeping
+// the recursion on untrusted sign data bounded
+pub const MAX_DEPTH: usize = 16;
+
+// the input has to hold exactly one item
+pub fn decode(data: &[u8]) -> Result<RlpItem, String> {
+    let (item, length) = decode_item(data, MAX_DEPTH)?;
+    match length == data.len() {
+        true => Ok(item),
+        false => Err(format!("{} trailing bytes after rlp item", data.len() - length)),
+    }
+}
+
+// returns the item and the number of bytes it occupies
+fn decode_item(data: &[u8], max_depth: usize) -> Result<(RlpItem, usize), String> {
+    let prefix = *data.first().ok_or("unexpected end of rlp data".to_string())?;
+    let (offset, length, is_list) = match prefix {
+        0x00..=0x7f => return Ok((RlpItem::Bytes(vec![prefix]), 1)),
+        0x80..=0xb7 => (1, (prefix - 0x80) as usize, false),
+        0xb8..=0xbf => read_long_length(data, (prefix - 0xb7) as usize, false)?,
+        0xc0..=0xf7 => (1, (prefix - 0xc0) as usize, true),
+        0xf8..=0xff => read_long_length(data, (prefix - 0xf7) as usize, true)?,
+    };
+    let end = offset
+        .checked_add(length)
+        .filter(|end| *end <= data.len())
+        .ok_or("unexpected end of rlp data".to_string())?;
+    let payload = &data[offset..end];
+    if !is_list && length == 1 && offset == 1 && payload[0] < 0x80 {
+        return Err("non-canonical rlp, single byte below 0x80 must not have a prefix".to_string());
+    }
+    if !is_list {
+        return Ok((RlpItem::Bytes(payload.to_vec()), end));
+    }
+    if max_depth == 0 {
+        return Err("rlp nesting exceeds the maximum depth".to_string());
+    }
+    let mut items = vec![];
+    let mut position = 0;
+    while position < payload.len() {
+        let (item, length) = decode_item(&payload[position..], max_depth - 1)?;
+        items.push(item);
+        position += length;
+    }
+    Ok((RlpItem::List(items), end))
+}
+
+fn read_long_length(
+    data: &[u8],
+    length_of_length: usize,
+    is_list: bool,
+) -> Result<(usize, usize, bool), String> {
+    let bytes = data
+        .get(1..1 + length_of_length)
+        .ok_or("unexpected end of rlp data".to_string())?;
+    if length_of_length > std::mem::size_of::<usize>() {
+        return Err("rlp length out of range".to_string());
+    }
+    if bytes[0] == 0 {
+        return Err("non-canonical rlp, length has leading zeros".to_string());
+    }
+    let length = bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+    if length < 56 {
+        return Err("non-canonical rlp, long form used for a length below 56".to_string());
+    }
+    Ok((1 + length_of_length, length, is_list))
+}
+
+// big endian unsigned integer, rlp encodes zero as an empty string and never with leading zeros
+pub fn decode_uint(item: &RlpItem, max_size: usize) -> Result<u128, String> {
+    let bytes = item.get_bytes()?;
+    if bytes.len() > max_size {
+        return Err(format!("rlp integer longer than {} bytes", max_size));
+    }
+    if bytes.first() == Some(&0) {
+        return Err("non-canonical rlp, integer has leading zeros".to_string());
+    }
+    Ok(bytes.iter().fold(0u128, |acc, b| (acc << 8) | *b as u128))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ethereum::rlp::{decode, decode_uint, RlpItem};
+    use hex::FromHex;
+
+    fn bytes(data: &[u8]) -> RlpItem {
+        RlpItem::Bytes(data.to_vec())
+    }
+
+    #[test]
+    fn test_decode() {
+        let decode_hex = |data: &str| decode(&Vec::from_hex(data).unwrap());
+        assert_eq!(Ok(bytes(b"dog")), decode_hex("83646f67"));
+        assert_eq!(
+            Ok(RlpItem::List(vec![bytes(b"cat"), bytes(b"dog")])),
+            decode_hex("c88363617483646f67")
+        );
+        assert_eq!(Ok(bytes(b"")), decode_hex("80"));
+        assert_eq!(Ok(RlpItem::List(vec![])), decode_hex("c0"));
+        assert_eq!(Ok(bytes(&[0x0f])), decode_hex("0f"));
+        assert_eq!(Ok(bytes(&[0x04, 0x00])), decode_hex("820400"));
+        // the set theoretical representation of three
+        let empty = || RlpItem::List(vec![]);
+        assert_eq!(
+            Ok(RlpItem::List(vec![
+                empty(),
+                RlpItem::List(vec![empty()]),
+                RlpItem::List(vec![empty(), RlpItem::List(vec![empty()])]),
+            ])),
+            decode_hex("c7c0c1c0c3c0c1c0")
+        );
+        let lorem = b"Lorem ipsum dolor sit amet, consectetur adipisicing elit";
+        let mut encoded = vec![0xb8, 0x38];
+        encoded.extend_from_slice(lorem);
+        assert_eq!(Ok(bytes(lorem)), decode(&encoded));
+    }
+
+    #[test]
+    fn test_decode_malformed() {
+        let decode_hex = |data: &str| decode(&Vec::from_hex(data).unwrap());
+        assert!(decode_hex("").is_err());
+        assert!(decode_hex("83646f").is_err());
+        assert!(decode_hex("c88363617483646f").is_err());
+        assert!(decode_hex("b838").is_err());
+        assert!(decode_hex("b9").is_err());
+        assert!(decode_hex("83646f6767").is_err());
+        assert!(decode_hex("8105").is_err());
+        assert!(decode_hex("b80100").is_err());
+        assert!(decode_hex("b9003800").is_err());
+    }
+
+    #[test]
+    fn test_decode_max_depth() {
+        // 17 lists, each holding the next one
+        let mut nested = vec![0xc0];
+        for _ in 0..16 {
+            let mut outer = vec![0xc0 + nested.len() as u8];
+            outer.extend(nested);
+            nested = outer;
+        }
+        assert_eq!(
+            Err("rlp nesting exceeds the maximum depth".to_string()),
+            decode(&nested)
+        );
+        assert!(decode(&nested[1..]).is_ok());
+    }
+
+    #[test]
+    fn test_decode_uint() {
+        assert_eq!(Ok(0), decode_uint(&bytes(&[]), 8));
+        assert_eq!(Ok(0x0400), decode_uint(&bytes(&[0x04, 0x00]), 8));
+        assert!(decode_uint(&bytes(&[0x00]), 8).is_err());
+        assert!(decode_uint(&bytes(&[0x00, 0x04]), 8).is_err());
+        assert!(decode_uint(&bytes(&[0x01; 9]), 8).is_err());
+    }
+}
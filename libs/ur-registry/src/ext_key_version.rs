@@ -0,0 +1,124 @@
+use crate::crypto_coin_info::{CoinType, Network};
+
+// the script a SLIP-132 prefix commits to, plain BIP32 keys use P2pkh
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScriptType {
+    P2pkh,
+    P2shP2wpkh,
+    P2wpkh,
+    P2shP2wsh,
+    P2wsh,
+}
+
+// (coin type, network, script type, is private key, version bytes)
+const VERSIONS: [(CoinType, Network, ScriptType, bool, [u8; 4]); 30] = [
+    (CoinType::Bitcoin, Network::MainNet, ScriptType::P2pkh, false, [0x04, 0x88, 0xb2, 0x1e]), // xpub
+    (CoinType::Bitcoin, Network::MainNet, ScriptType::P2pkh, true, [0x04, 0x88, 0xad, 0xe4]), // xprv
+    (CoinType::Bitcoin, Network::MainNet, ScriptType::P2shP2wpkh, false, [0x04, 0x9d, 0x7c, 0xb2]), // ypub
+    (CoinType::Bitcoin, Network::MainNet, ScriptType::P2shP2wpkh, true, [0x04, 0x9d, 0x78, 0x78]), // yprv
+    (CoinType::Bitcoin, Network::MainNet, ScriptType::P2wpkh, false, [0x04, 0xb2, 0x47, 0x46]), // zpub
+    (CoinType::Bitcoin, Network::MainNet, ScriptType::P2wpkh, true, [0x04, 0xb2, 0x43, 0x0c]), // zprv
+    (CoinType::Bitcoin, Network::MainNet, ScriptType::P2shP2wsh, false, [0x02, 0x95, 0xb4, 0x3f]), // Ypub
+    (CoinType::Bitcoin, Network::MainNet, ScriptType::P2shP2wsh, true, [0x02, 0x95, 0xb0, 0x05]), // Yprv
+    (CoinType::Bitcoin, Network::MainNet, ScriptType::P2wsh, false, [0x02, 0xaa, 0x7e, 0xd3]), // Zpub
+    (CoinType::Bitcoin, Network::MainNet, ScriptType::P2wsh, true, [0x02, 0xaa, 0x7a, 0x99]), // Zprv
+    (CoinType::Bitcoin, Network::TestNet, ScriptType::P2pkh, false, [0x04, 0x35, 0x87, 0xcf]), // tpub
+    (CoinType::Bitcoin, Network::TestNet, ScriptType::P2pkh, true, [0x04, 0x35, 0x83, 0x94]), // tprv
+    (CoinType::Bitcoin, Network::TestNet, ScriptType::P2shP2wpkh, false, [0x04, 0x4a, 0x52, 0x62]), // upub
+    (CoinType::Bitcoin, Network::TestNet, ScriptType::P2shP2wpkh, true, [0x04, 0x4a, 0x4e, 0x28]), // uprv
+    (CoinType::Bitcoin, Network::TestNet, ScriptType::P2wpkh, false, [0x04, 0x5f, 0x1c, 0xf6]), // vpub
+    (CoinType::Bitcoin, Network::TestNet, ScriptType::P2wpkh, true, [0x04, 0x5f, 0x18, 0xbc]), // vprv
+    (CoinType::Bitcoin, Network::TestNet, ScriptType::P2shP2wsh, false, [0x02, 0x42, 0x89, 0xef]), // Upub
+    (CoinType::Bitcoin, Network::TestNet, ScriptType::P2shP2wsh, true, [0x02, 0x42, 0x85, 0xb5]), // Uprv
+    (CoinType::Bitcoin, Network::TestNet, ScriptType::P2wsh, false, [0x02, 0x57, 0x54, 0x83]), // Vpub
+    (CoinType::Bitcoin, Network::TestNet, ScriptType::P2wsh, true, [0x02, 0x57, 0x50, 0x48]), // Vprv
+    (CoinType::Litecoin, Network::MainNet, ScriptType::P2pkh, false, [0x01, 0x9d, 0xa4, 0x62]), // Ltub
+    (CoinType::Litecoin, Network::MainNet, ScriptType::P2pkh, true, [0x01, 0x9d, 0x9c, 0xfe]), // Ltpv
+    (CoinType::Litecoin, Network::MainNet, ScriptType::P2shP2wpkh, false, [0x01, 0xb2, 0x6e, 0xf6]), // Mtub
+    (CoinType::Litecoin, Network::MainNet, ScriptType::P2shP2wpkh, true, [0x01, 0xb2, 0x67, 0x92]), // Mtpv
+    (CoinType::Litecoin, Network::TestNet, ScriptType::P2pkh, false, [0x04, 0x36, 0xf6, 0xe1]), // ttub
+    (CoinType::Litecoin, Network::TestNet, ScriptType::P2pkh, true, [0x04, 0x36, 0xef, 0x7d]), // ttpv
+    (CoinType::Dogecoin, Network::MainNet, ScriptType::P2pkh, false, [0x02, 0xfa, 0xca, 0xfd]), // dgub
+    (CoinType::Dogecoin, Network::MainNet, ScriptType::P2pkh, true, [0x02, 0xfa, 0xc3, 0x98]), // dgpv
+    (CoinType::Dogecoin, Network::TestNet, ScriptType::P2pkh, false, [0x04, 0x32, 0xa9, 0xa8]), // tgub
+    (CoinType::Dogecoin, Network::TestNet, ScriptType::P2pkh, true, [0x04, 0x32, 0xa2, 0x43]), // tgpv
+];
+
+pub fn get_version(
+    coin_type: &CoinType,
+    network: &Network,
+    script_type: ScriptType,
+    is_private_key: bool,
+) -> Option<[u8; 4]> {
+    VERSIONS
+        .iter()
+        .find(|(c, n, s, p, _)| {
+            c == coin_type && n == network && *s == script_type && *p == is_private_key
+        })
+        .map(|(_, _, _, _, version)| *version)
+}
+
+// the first match wins, so shared prefixes like xpub resolve to Bitcoin
+pub fn parse_version(version: [u8; 4]) -> Option<(CoinType, Network, ScriptType, bool)> {
+    VERSIONS
+        .iter()
+        .find(|(_, _, _, _, v)| *v == version)
+        .map(|(c, n, s, p, _)| (c.clone(), n.clone(), *s, *p))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto_coin_info::{CoinType, Network};
+    use crate::ext_key_version::{get_version, parse_version, ScriptType};
+    use hex::FromHex;
+
+    // m/0' of BIP32 test vector 1 without the version, private keys carry a leading zero
+    fn serialize(version: [u8; 4], is_private_key: bool) -> String {
+        let key = match is_private_key {
+            true => "00edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea",
+            false => "035a784662a4a20a65bf6aab9ae98a6c068a81c52e4b032c0fb5400c706cfccc56",
+        };
+        let mut data = version.to_vec();
+        data.extend(Vec::from_hex("013442193e8000000047fdacbd0f1097043b78c63c20c34ef4ed9a111d980047ad16282c7ae6236141").unwrap());
+        data.extend(Vec::from_hex(key).unwrap());
+        bs58::encode(data).with_check().into_string()
+    }
+
+    #[test]
+    fn test_get_version() {
+        let cases = [
+            (CoinType::Bitcoin, Network::MainNet, ScriptType::P2pkh, false, "xpub"),
+            (CoinType::Bitcoin, Network::MainNet, ScriptType::P2pkh, true, "xprv"),
+            (CoinType::Bitcoin, Network::MainNet, ScriptType::P2shP2wpkh, false, "ypub"),
+            (CoinType::Bitcoin, Network::MainNet, ScriptType::P2wpkh, false, "zpub"),
+            (CoinType::Bitcoin, Network::TestNet, ScriptType::P2pkh, false, "tpub"),
+            (CoinType::Litecoin, Network::MainNet, ScriptType::P2pkh, false, "Ltub"),
+        ];
+        for (coin_type, network, script_type, is_private_key, prefix) in cases {
+            let version = get_version(&coin_type, &network, script_type, is_private_key).unwrap();
+            assert!(serialize(version, is_private_key).starts_with(prefix));
+        }
+        assert_eq!(
+            "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw",
+            serialize([0x04, 0x88, 0xb2, 0x1e], false)
+        );
+        assert_eq!(None, get_version(&CoinType::Dogecoin, &Network::MainNet, ScriptType::P2wpkh, false));
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            Some((CoinType::Bitcoin, Network::MainNet, ScriptType::P2wpkh, false)),
+            parse_version([0x04, 0xb2, 0x47, 0x46])
+        );
+        assert_eq!(
+            Some((CoinType::Litecoin, Network::MainNet, ScriptType::P2pkh, false)),
+            parse_version([0x01, 0x9d, 0xa4, 0x62])
+        );
+        assert_eq!(
+            Some((CoinType::Bitcoin, Network::MainNet, ScriptType::P2pkh, true)),
+            parse_version([0x04, 0x88, 0xad, 0xe4])
+        );
+        assert_eq!(None, parse_version([0, 0, 0, 0]));
+    }
+}
@@ -1,9 +1,9 @@
-use crate::cbor_value::CborValue;
+use crate::cbor_value::{log_decode_error, log_field_error, CborValue};
 use crate::crypto_hd_key::CryptoHDKey;
 use crate::registry_types::{RegistryType, CRYPTO_HDKEY, CRYPTO_MULTI_ACCOUNTS};
 use crate::traits::{From, RegistryItem, To};
 use crate::types::Fingerprint;
-use serde_cbor::{from_slice, to_vec, Value};
+use crate::cbor::{decode, encode, Value};
 use std::collections::BTreeMap;
 
 const MASTER_FINGERPRINT: i128 = 1;
@@ -72,12 +72,13 @@ impl To for CryptoMultiAccounts {
         let mut map: BTreeMap<Value, Value> = BTreeMap::new();
         map.insert(
             Value::Integer(MASTER_FINGERPRINT),
-            Value::Integer(u32::from_be_bytes(self.get_master_fingerprint()) as i128),
+            Value::Integer(u32::from_be_bytes(self.master_fingerprint) as i128),
         );
+        // the fields are borrowed, get_keys would clone every key just to encode it
         map.insert(
             Value::Integer(KEYS),
             Value::Array(
-                self.get_keys()
+                self.keys
                     .iter()
                     .map(|key| {
                         Value::Tag(
@@ -88,44 +89,49 @@ impl To for CryptoMultiAccounts {
                     .collect(),
             ),
         );
-        self.get_device()
-            .and_then(|device| map.insert(Value::Integer(DEVICE), Value::Text(device)));
+        if let Some(device) = &self.device {
+            map.insert(Value::Integer(DEVICE), Value::Text(device.clone()));
+        }
         Value::Map(map)
     }
 
     fn to_bytes(&self) -> Vec<u8> {
         let value = self.to_cbor();
-        to_vec(&value).unwrap()
+        encode(&value).unwrap()
     }
 }
 
 impl From<CryptoMultiAccounts> for CryptoMultiAccounts {
     fn from_cbor(cbor: Value) -> Result<CryptoMultiAccounts, String> {
         let value = CborValue::new(cbor);
-        let map = value.get_map()?;
+        let map = value.get_map().map_err(log_decode_error::<CryptoMultiAccounts>)?;
         let master_fingerprint = map
             .get_by_integer(MASTER_FINGERPRINT)
             .map_or(
                 Err("master_fingerprint is required for crypto-multi-accounts".to_string()),
                 |v| v.get_integer(),
             )
-            .map(|v| (v as u32).to_be_bytes())?;
+            .map(|v| (v as u32).to_be_bytes())
+            .map_err(log_field_error::<CryptoMultiAccounts>(MASTER_FINGERPRINT))?;
         let keys = map
             .get_by_integer(KEYS)
             .map_or(
                 Err("keys is required for crypto-multi-accounts".to_string()),
                 |v| v.get_array(),
-            )?
+            )
+            .map_err(log_field_error::<CryptoMultiAccounts>(KEYS))?
             .iter()
             .map(|v| {
                 v.get_tag(CRYPTO_HDKEY.get_tag())
                     .and_then(|v| CryptoHDKey::from_cbor(v.get_value().clone()))
             })
-            .collect::<Result<Vec<CryptoHDKey>, String>>()?;
+            .collect::<Result<Vec<CryptoHDKey>, String>>()
+            .map_err(log_field_error::<CryptoMultiAccounts>(KEYS))?;
         let device = map
             .get_by_integer(DEVICE)
             .map(|v| v.get_text())
-            .transpose()?;
+            .transpose()
+            .map_err(log_field_error::<CryptoMultiAccounts>(DEVICE))?;
         Ok(CryptoMultiAccounts {
             master_fingerprint,
             keys,
@@ -134,10 +140,7 @@ impl From<CryptoMultiAccounts> for CryptoMultiAccounts {
     }
 
     fn from_bytes(bytes: Vec<u8>) -> Result<CryptoMultiAccounts, String> {
-        let value: Value = match from_slice(bytes.as_slice()) {
-            Ok(x) => x,
-            Err(e) => return Err(e.to_string()),
-        };
+        let value = decode(bytes.as_slice())?;
         CryptoMultiAccounts::from_cbor(value)
     }
 }
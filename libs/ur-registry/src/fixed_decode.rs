@@ -0,0 +1,367 @@
+// allocation free decoding of the small fixed size types for signers without a heap. the decoded key path
+// components are written into a buffer provided by the caller and inputs which don't fit are rejected,
+// errors are static strings for the same reason.
+use crate::cbor::MAX_DEPTH;
+use crate::crypto_coin_info::CryptoCoinInfo;
+use crate::crypto_key_path::PathComponent;
+use crate::registry_types::{normalize_tag, CRYPTO_COIN_INFO, CRYPTO_KEYPATH};
+use crate::types::Fingerprint;
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+
+const SIMPLE_FALSE: u64 = 20;
+const SIMPLE_TRUE: u64 = 21;
+
+// coin-info keys
+const COIN_TYPE: u64 = 1;
+const NETWORK: u64 = 2;
+// keypath keys
+const COMPONENTS: u64 = 1;
+const SOURCE_FINGERPRINT: u64 = 2;
+const DEPTH: u64 = 3;
+// hdkey keys
+const IS_MASTER: u64 = 1;
+const IS_PRIVATE: u64 = 2;
+const KEY_DATA: u64 = 3;
+const CHAIN_CODE: u64 = 4;
+const USE_INFO: u64 = 5;
+const ORIGIN: u64 = 6;
+const PARENT_FINGERPRINT: u64 = 8;
+
+#[derive(Debug)]
+pub struct FixedKeyPath<'b> {
+    pub components: &'b [PathComponent],
+    pub source_fingerprint: Option<Fingerprint>,
+    pub depth: Option<u32>,
+}
+
+// children, name and note are skipped
+#[derive(Debug)]
+pub struct FixedHDKey<'b> {
+    pub is_master: bool,
+    pub is_private_key: bool,
+    pub key: [u8; 33],
+    pub chain_code: Option<[u8; 32]>,
+    pub use_info: Option<CryptoCoinInfo>,
+    pub origin: Option<FixedKeyPath<'b>>,
+    pub parent_fingerprint: Option<Fingerprint>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, offset: 0 }
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], &'static str> {
+        let end = self
+            .offset
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or("unexpected end of cbor data")?;
+        let slice = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(slice)
+    }
+
+    // a u64 length doesn't fit usize on 32 bit targets, it must fail rather than be truncated
+    fn take_length(&mut self, length: u64) -> Result<&'a [u8], &'static str> {
+        self.take(usize::try_from(length).map_err(|_| "cbor length out of range")?)
+    }
+
+    fn read_head(&mut self) -> Result<(u8, u64), &'static str> {
+        let initial = self.take(1)?[0];
+        let major_type = initial >> 5;
+        let argument = match initial & 0x1f {
+            x @ 0..=23 => x as u64,
+            24 => self.take(1)?[0] as u64,
+            25 => self
+                .take(2)?
+                .iter()
+                .fold(0, |acc, b| (acc << 8) | *b as u64),
+            26 => self
+                .take(4)?
+                .iter()
+                .fold(0, |acc, b| (acc << 8) | *b as u64),
+            27 => self
+                .take(8)?
+                .iter()
+                .fold(0, |acc, b| (acc << 8) | *b as u64),
+            _ => return Err("indefinite length cbor items are not supported"),
+        };
+        Ok((major_type, argument))
+    }
+
+    fn expect(&mut self, major_type: u8) -> Result<u64, &'static str> {
+        match self.read_head()? {
+            (x, argument) if x == major_type => Ok(argument),
+            _ => Err("unexpected cbor major type"),
+        }
+    }
+
+    fn read_uint(&mut self) -> Result<u64, &'static str> {
+        self.expect(MAJOR_UNSIGNED)
+    }
+
+    fn read_bool(&mut self) -> Result<bool, &'static str> {
+        match self.expect(MAJOR_SIMPLE)? {
+            SIMPLE_FALSE => Ok(false),
+            SIMPLE_TRUE => Ok(true),
+            _ => Err("expected a boolean"),
+        }
+    }
+
+    fn read_bytes(&mut self) -> Result<&'a [u8], &'static str> {
+        let length = self.expect(MAJOR_BYTES)?;
+        self.take_length(length)
+    }
+
+    fn read_tag(&mut self, tag: u64) -> Result<(), &'static str> {
+        match normalize_tag(self.expect(MAJOR_TAG)?) == tag {
+            true => Ok(()),
+            false => Err("unexpected cbor tag"),
+        }
+    }
+
+    fn skip(&mut self) -> Result<(), &'static str> {
+        self.skip_nested(MAX_DEPTH)
+    }
+
+    // skipped fields can nest arbitrarily deep, the budget bounds the recursion on small stacks
+    fn skip_nested(&mut self, max_depth: usize) -> Result<(), &'static str> {
+        let (major_type, argument) = self.read_head()?;
+        if matches!(major_type, MAJOR_ARRAY | MAJOR_MAP | MAJOR_TAG) && max_depth == 0 {
+            return Err("cbor nesting exceeds the maximum depth");
+        }
+        match major_type {
+            MAJOR_UNSIGNED | MAJOR_NEGATIVE | MAJOR_SIMPLE => Ok(()),
+            MAJOR_BYTES | MAJOR_TEXT => self.take_length(argument).map(|_| ()),
+            MAJOR_ARRAY => (0..argument).try_for_each(|_| self.skip_nested(max_depth - 1)),
+            MAJOR_MAP => (0..argument).try_for_each(|_| {
+                self.skip_nested(max_depth - 1)
+                    .and_then(|_| self.skip_nested(max_depth - 1))
+            }),
+            MAJOR_TAG => self.skip_nested(max_depth - 1),
+            _ => Err("invalid cbor major type"),
+        }
+    }
+}
+
+pub fn decode_coin_info(bytes: &[u8]) -> Result<CryptoCoinInfo, &'static str> {
+    read_coin_info(&mut Reader::new(bytes))
+}
+
+pub fn decode_key_path<'b>(
+    bytes: &[u8],
+    components: &'b mut [PathComponent],
+) -> Result<FixedKeyPath<'b>, &'static str> {
+    read_key_path(&mut Reader::new(bytes), components)
+}
+
+pub fn decode_hd_key<'b>(
+    bytes: &[u8],
+    components: &'b mut [PathComponent],
+) -> Result<FixedHDKey<'b>, &'static str> {
+    let mut reader = Reader::new(bytes);
+    let mut hd_key = FixedHDKey {
+        is_master: false,
+        is_private_key: false,
+        key: [0; 33],
+        chain_code: None,
+        use_info: None,
+        origin: None,
+        parent_fingerprint: None,
+    };
+    let mut has_key = false;
+    let mut components = Some(components);
+    for _ in 0..reader.expect(MAJOR_MAP)? {
+        match reader.read_uint()? {
+            IS_MASTER => hd_key.is_master = reader.read_bool()?,
+            IS_PRIVATE => hd_key.is_private_key = reader.read_bool()?,
+            KEY_DATA => {
+                hd_key.key = reader
+                    .read_bytes()?
+                    .try_into()
+                    .map_err(|_| "key data must be 33 bytes")?;
+                has_key = true;
+            }
+            CHAIN_CODE => {
+                hd_key.chain_code = Some(
+                    reader
+                        .read_bytes()?
+                        .try_into()
+                        .map_err(|_| "chain code must be 32 bytes")?,
+                )
+            }
+            USE_INFO => {
+                reader.read_tag(CRYPTO_COIN_INFO.get_tag())?;
+                hd_key.use_info = Some(read_coin_info(&mut reader)?);
+            }
+            ORIGIN => {
+                reader.read_tag(CRYPTO_KEYPATH.get_tag())?;
+                let components = components
+                    .take()
+                    .ok_or("duplicate origin in crypto-hdkey")?;
+                hd_key.origin = Some(read_key_path(&mut reader, components)?);
+            }
+            PARENT_FINGERPRINT => hd_key.parent_fingerprint = Some(read_fingerprint(&mut reader)?),
+            _ => reader.skip()?,
+        }
+    }
+    match has_key {
+        true => Ok(hd_key),
+        false => Err("key data is required for crypto-hdkey"),
+    }
+}
+
+fn read_coin_info(reader: &mut Reader) -> Result<CryptoCoinInfo, &'static str> {
+    let mut coin_type = None;
+    let mut network = None;
+    for _ in 0..reader.expect(MAJOR_MAP)? {
+        match reader.read_uint()? {
+            COIN_TYPE => {
+                coin_type = Some(u32::try_from(reader.read_uint()?).map_err(|_| "coin type out of range")?)
+            }
+            NETWORK => network = Some(reader.read_uint()? as i128),
+            _ => reader.skip()?,
+        }
+    }
+    Ok(CryptoCoinInfo::new_raw(coin_type, network))
+}
+
+fn read_key_path<'b>(
+    reader: &mut Reader,
+    components: &'b mut [PathComponent],
+) -> Result<FixedKeyPath<'b>, &'static str> {
+    let mut count = 0;
+    let mut source_fingerprint = None;
+    let mut depth = None;
+    for _ in 0..reader.expect(MAJOR_MAP)? {
+        match reader.read_uint()? {
+            COMPONENTS => {
+                let length = reader.expect(MAJOR_ARRAY)?;
+                if length % 2 != 0 {
+                    return Err("key path components must come in index and hardened pairs");
+                }
+                count = usize::try_from(length / 2).map_err(|_| "key path exceeds the provided buffer")?;
+                if count > components.len() {
+                    return Err("key path exceeds the provided buffer");
+                }
+                for component in components.iter_mut().take(count) {
+                    let index = match reader.read_head()? {
+                        (MAJOR_UNSIGNED, x) if x < PathComponent::HARDEN_BIT as u64 => {
+                            Some(x as u32)
+                        }
+                        (MAJOR_ARRAY, 0) => None,
+                        _ => return Err("invalid key path component"),
+                    };
+                    let hardened = reader.read_bool()?;
+                    *component = PathComponent::new(index, hardened)
+                        .map_err(|_| "invalid key path component")?;
+                }
+            }
+            SOURCE_FINGERPRINT => source_fingerprint = Some(read_fingerprint(reader)?),
+            DEPTH => {
+                depth = Some(u32::try_from(reader.read_uint()?).map_err(|_| "depth out of range")?)
+            }
+            _ => reader.skip()?,
+        }
+    }
+    Ok(FixedKeyPath {
+        components: &components[..count],
+        source_fingerprint,
+        depth,
+    })
+}
+
+fn read_fingerprint(reader: &mut Reader) -> Result<Fingerprint, &'static str> {
+    u32::try_from(reader.read_uint()?)
+        .map(u32::to_be_bytes)
+        .map_err(|_| "fingerprint out of range")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto_coin_info::Network;
+    use crate::crypto_hd_key::TEST_HD_KEY_HEX;
+    use crate::crypto_key_path::PathComponent;
+    use crate::fixed_decode::{decode_coin_info, decode_hd_key, decode_key_path};
+    use hex::FromHex;
+
+    #[test]
+    fn test_decode_hd_key() {
+        let bytes = Vec::from_hex(TEST_HD_KEY_HEX).unwrap();
+        let mut components = [PathComponent::new(Some(0), false).unwrap(); 8];
+        let hd_key = decode_hd_key(&bytes, &mut components).unwrap();
+        assert_eq!(false, hd_key.is_master);
+        assert_eq!(
+            "026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6",
+            hex::encode(hd_key.key)
+        );
+        assert_eq!(
+            "ced155c72456255881793514edc5bd9447e7f74abb88c6d6b6480fd016ee8c85",
+            hex::encode(hd_key.chain_code.unwrap())
+        );
+        assert_eq!(Network::TestNet, hd_key.use_info.unwrap().get_network());
+        let origin = hd_key.origin.unwrap();
+        assert_eq!(5, origin.components.len());
+        assert_eq!(Some(44), origin.components[0].get_index());
+        assert_eq!(true, origin.components[0].is_hardened());
+        assert_eq!(Some([0xe9, 0x18, 0x1c, 0xf3]), hd_key.parent_fingerprint);
+
+        let mut small = [PathComponent::new(Some(0), false).unwrap(); 4];
+        assert_eq!(
+            "key path exceeds the provided buffer",
+            decode_hd_key(&bytes, &mut small).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_decode_key_path_and_coin_info() {
+        let mut components = [PathComponent::new(Some(0), false).unwrap(); 4];
+        let key_path = decode_key_path(
+            &Vec::from_hex("a3018a182cf501f501f500f401f4021ad34db33f0305").unwrap(),
+            &mut components,
+        );
+        assert!(key_path.is_err());
+
+        let mut components = [PathComponent::new(Some(0), false).unwrap(); 5];
+        let key_path = decode_key_path(
+            &Vec::from_hex("a3018a182cf501f501f500f401f4021ad34db33f0305").unwrap(),
+            &mut components,
+        )
+        .unwrap();
+        assert_eq!(5, key_path.components.len());
+        assert_eq!(Some([0xd3, 0x4d, 0xb3, 0x3f]), key_path.source_fingerprint);
+        assert_eq!(Some(5), key_path.depth);
+
+        let coin_info = decode_coin_info(&Vec::from_hex("a201000201").unwrap()).unwrap();
+        assert_eq!(Network::TestNet, coin_info.get_network());
+    }
+
+    #[test]
+    fn test_skip_limits() {
+        let mut components = [PathComponent::new(Some(0), false).unwrap(); 4];
+        let hd_key = |unknown_field: &str| {
+            Vec::from_hex(format!("a2035821{}09{}", "02".repeat(33), unknown_field)).unwrap()
+        };
+        assert!(decode_hd_key(&hd_key("c6c600"), &mut components).is_ok());
+        // thousands of nested tags in an unknown field must not exhaust the stack
+        assert_eq!(
+            "cbor nesting exceeds the maximum depth",
+            decode_hd_key(&hd_key(&format!("{}00", "c6".repeat(4000))), &mut components).unwrap_err()
+        );
+        assert!(decode_hd_key(&hd_key("5b000000010000000501"), &mut components).is_err());
+    }
+}
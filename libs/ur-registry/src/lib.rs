@@ -1,5 +1,6 @@
 extern crate core;
 
+pub mod cbor;
 mod cbor_value;
 pub mod crypto_coin_info;
 pub mod crypto_ec_key;
@@ -12,5 +13,12 @@ pub mod ethereum;
 pub mod traits;
 mod types;
 pub mod crypto_output;
+pub mod script_expression;
 pub mod crypto_account;
 pub mod crypto_psbt;
+pub mod ur_decoder;
+pub mod ur_encoder;
+pub mod crypto;
+pub mod fixed_decode;
+pub mod ext_key_version;
+pub mod request_id;
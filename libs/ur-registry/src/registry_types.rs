@@ -1,9 +1,12 @@
 pub struct RegistryType<'a>(&'a str, u64);
 
-impl<'a> RegistryType<'_> {
+impl<'a> RegistryType<'a> {
     pub fn get_type(&self) -> String {
         self.0.to_string()
     }
+    pub fn get_type_str(&self) -> &'a str {
+        self.0
+    }
     pub fn get_tag(&self) -> u64 {
         self.1
     }
@@ -25,3 +28,66 @@ pub const ETH_SIGNATURE: RegistryType = RegistryType("eth-signature", 402);
 pub const SOL_SIGN_REQUEST: RegistryType = RegistryType("sol-sign-request", 1101);
 pub const SOL_SIGNATURE: RegistryType = RegistryType("sol-signature", 1102);
 pub const CRYPTO_MULTI_ACCOUNTS: RegistryType = RegistryType("crypto-multi-accounts", 1103);
+
+// the bitcoin types were re-registered with 40000 added to their tags (crypto-hdkey 303 -> 40303),
+// both forms are accepted on decode and normalized to the tags above
+pub fn normalize_tag(tag: u64) -> u64 {
+    match tag {
+        40303..=40311 => tag - 40000,
+        x => x,
+    }
+}
+
+pub const REGISTRY_TYPES: [RegistryType; 13] = [
+    UUID,
+    CRYPTO_HDKEY,
+    CRYPTO_KEYPATH,
+    CRYPTO_COIN_INFO,
+    CRYPTO_ECKEY,
+    CRYPTO_OUTPUT,
+    CRYPTO_PSBT,
+    CRYPTO_ACCOUNT,
+    ETH_SIGN_REQUEST,
+    ETH_SIGNATURE,
+    SOL_SIGN_REQUEST,
+    SOL_SIGNATURE,
+    CRYPTO_MULTI_ACCOUNTS,
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::registry_types::{normalize_tag, REGISTRY_TYPES};
+
+    #[test]
+    fn test_registry_tags() {
+        let expected = [
+            ("uuid", 37),
+            ("crypto-hdkey", 303),
+            ("crypto-keypath", 304),
+            ("crypto-coin-info", 305),
+            ("crypto-eckey", 306),
+            ("crypto-output", 308),
+            ("crypto-psbt", 310),
+            ("crypto-account", 311),
+            ("eth-sign-request", 401),
+            ("eth-signature", 402),
+            ("sol-sign-request", 1101),
+            ("sol-signature", 1102),
+            ("crypto-multi-accounts", 1103),
+        ];
+        assert_eq!(expected.len(), REGISTRY_TYPES.len());
+        for (registry_type, (ur_type, tag)) in REGISTRY_TYPES.iter().zip(expected.iter()) {
+            assert_eq!(*ur_type, registry_type.get_type());
+            assert_eq!(*tag, registry_type.get_tag());
+        }
+    }
+
+    #[test]
+    fn test_normalize_tag() {
+        assert_eq!(303, normalize_tag(40303));
+        assert_eq!(305, normalize_tag(40305));
+        assert_eq!(303, normalize_tag(303));
+        assert_eq!(37, normalize_tag(37));
+        assert_eq!(401, normalize_tag(401));
+    }
+}
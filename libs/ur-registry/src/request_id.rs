@@ -0,0 +1,55 @@
+use crate::types::Bytes;
+
+const REQUEST_ID_LENGTH: usize = 16;
+
+// the 16 byte uuid sign requests and signatures are matched by
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RequestId([u8; REQUEST_ID_LENGTH]);
+
+impl RequestId {
+    pub fn from_bytes(bytes: Bytes) -> Result<RequestId, String> {
+        bytes.as_slice().try_into().map(RequestId).map_err(|_| {
+            format!(
+                "invalid request id length {}, expected {} bytes",
+                bytes.len(),
+                REQUEST_ID_LENGTH
+            )
+        })
+    }
+
+    // a version 4 uuid
+    #[cfg(feature = "rand")]
+    pub fn random() -> RequestId {
+        let mut bytes: [u8; REQUEST_ID_LENGTH] = rand::random();
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        RequestId(bytes)
+    }
+
+    pub fn get_bytes(&self) -> Bytes {
+        self.0.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::request_id::RequestId;
+    use hex::FromHex;
+
+    #[test]
+    fn test_from_bytes() {
+        let bytes = Vec::from_hex("9b1deb4d3b7d4bad9bdd2b0d7b3dcb6d").unwrap();
+        assert_eq!(bytes, RequestId::from_bytes(bytes.clone()).unwrap().get_bytes());
+        assert!(RequestId::from_bytes(vec![0; 15]).is_err());
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_random() {
+        let first = RequestId::random();
+        let second = RequestId::random();
+        assert_ne!(first, second);
+        assert_eq!(16, first.get_bytes().len());
+        assert_eq!(0x40, first.get_bytes()[6] & 0xf0);
+    }
+}
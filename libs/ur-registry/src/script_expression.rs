@@ -0,0 +1,82 @@
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScriptExpression {
+    ScriptHash,
+    WitnessScriptHash,
+    PublicKey,
+    PublicKeyHash,
+    WitnessPublicKeyHash,
+    Combo,
+    MultiSig,
+    SortedMultiSig,
+    RawScript,
+    Address,
+    Taproot,
+    Cosigner,
+    // a tag this crate doesn't model, kept so relayed outputs encode back unchanged
+    Unknown(u64),
+}
+
+const SCRIPT_EXPRESSIONS: [ScriptExpression; 12] = [
+    ScriptExpression::ScriptHash,
+    ScriptExpression::WitnessScriptHash,
+    ScriptExpression::PublicKey,
+    ScriptExpression::PublicKeyHash,
+    ScriptExpression::WitnessPublicKeyHash,
+    ScriptExpression::Combo,
+    ScriptExpression::MultiSig,
+    ScriptExpression::SortedMultiSig,
+    ScriptExpression::RawScript,
+    ScriptExpression::Address,
+    ScriptExpression::Taproot,
+    ScriptExpression::Cosigner,
+];
+
+impl ScriptExpression {
+    pub fn get_tag(&self) -> u64 {
+        match self {
+            ScriptExpression::ScriptHash => 400,
+            ScriptExpression::WitnessScriptHash => 401,
+            ScriptExpression::PublicKey => 402,
+            ScriptExpression::PublicKeyHash => 403,
+            ScriptExpression::WitnessPublicKeyHash => 404,
+            ScriptExpression::Combo => 405,
+            ScriptExpression::MultiSig => 406,
+            ScriptExpression::SortedMultiSig => 407,
+            ScriptExpression::RawScript => 408,
+            ScriptExpression::Address => 307,
+            ScriptExpression::Taproot => 409,
+            ScriptExpression::Cosigner => 410,
+            ScriptExpression::Unknown(tag) => *tag,
+        }
+    }
+
+    pub fn get_expression(&self) -> &'static str {
+        match self {
+            ScriptExpression::ScriptHash => "sh",
+            ScriptExpression::WitnessScriptHash => "wsh",
+            ScriptExpression::PublicKey => "pk",
+            ScriptExpression::PublicKeyHash => "pkh",
+            ScriptExpression::WitnessPublicKeyHash => "wpkh",
+            ScriptExpression::Combo => "combo",
+            ScriptExpression::MultiSig => "multi",
+            ScriptExpression::SortedMultiSig => "sortedmulti",
+            ScriptExpression::RawScript => "raw",
+            ScriptExpression::Address => "addr",
+            ScriptExpression::Taproot => "tr",
+            ScriptExpression::Cosigner => "cosigner",
+            ScriptExpression::Unknown(_) => "unknown",
+        }
+    }
+
+    // only the modelled expressions, see `from_tag_or_unknown` for pass-through decoding
+    pub fn from_tag(tag: u64) -> Option<ScriptExpression> {
+        SCRIPT_EXPRESSIONS
+            .iter()
+            .find(|expression| expression.get_tag() == tag)
+            .cloned()
+    }
+
+    pub fn from_tag_or_unknown(tag: u64) -> ScriptExpression {
+        ScriptExpression::from_tag(tag).unwrap_or(ScriptExpression::Unknown(tag))
+    }
+}
@@ -1,9 +1,9 @@
-use crate::cbor_value::CborValue;
+use crate::cbor_value::{log_decode_error, log_field_error, CborValue};
 use crate::crypto_key_path::CryptoKeyPath;
 use crate::registry_types::{RegistryType, CRYPTO_KEYPATH, SOL_SIGN_REQUEST, UUID};
-use crate::traits::{From, RegistryItem, To};
+use crate::traits::{From, RegistryItem, RequestOrigin, To};
 use crate::types::Bytes;
-use serde_cbor::{from_slice, to_vec, Value};
+use crate::cbor::{decode, encode, Value};
 use std::collections::BTreeMap;
 
 const REQUEST_ID: i128 = 1;
@@ -114,6 +114,12 @@ impl SolSignRequest {
     }
 }
 
+impl RequestOrigin for SolSignRequest {
+    fn get_origin(&self) -> Option<String> {
+        self.origin.clone()
+    }
+}
+
 impl RegistryItem for SolSignRequest {
     fn get_registry_type() -> RegistryType<'static> {
         SOL_SIGN_REQUEST
@@ -153,44 +159,54 @@ impl To for SolSignRequest {
 
     fn to_bytes(&self) -> Vec<u8> {
         let value = self.to_cbor();
-        to_vec(&value).unwrap()
+        encode(&value).unwrap()
     }
 }
 
 impl From<SolSignRequest> for SolSignRequest {
     fn from_cbor(cbor: Value) -> Result<SolSignRequest, String> {
         let value = CborValue::new(cbor);
-        let map = value.get_map()?;
+        let map = value.get_map().map_err(log_decode_error::<SolSignRequest>)?;
         let request_id = map
             .get_by_integer(REQUEST_ID)
             .map(|v| v.get_tag(UUID.get_tag()).and_then(|v| v.get_bytes()))
-            .transpose()?;
-        let sign_data = map.get_by_integer(SIGN_DATA).map_or(
-            Err("sign_data is required for sol-sign-request".to_string()),
-            |v| v.get_bytes(),
-        )?;
-        let derivation_path = map.get_by_integer(DERIVATION_PATH).map_or(
-            Err("derivation_path is required for sol-sign-request".to_string()),
-            |v| {
-                v.get_tag(CRYPTO_KEYPATH.get_tag())
-                    .and_then(|v| CryptoKeyPath::from_cbor(v.get_value().clone()))
-            },
-        )?;
+            .transpose()
+            .map_err(log_field_error::<SolSignRequest>(REQUEST_ID))?;
+        let sign_data = map
+            .get_by_integer(SIGN_DATA)
+            .map_or(
+                Err("sign_data is required for sol-sign-request".to_string()),
+                |v| v.get_bytes(),
+            )
+            .map_err(log_field_error::<SolSignRequest>(SIGN_DATA))?;
+        let derivation_path = map
+            .get_by_integer(DERIVATION_PATH)
+            .map_or(
+                Err("derivation_path is required for sol-sign-request".to_string()),
+                |v| {
+                    v.get_tag(CRYPTO_KEYPATH.get_tag())
+                        .and_then(|v| CryptoKeyPath::from_cbor(v.get_value().clone()))
+                },
+            )
+            .map_err(log_field_error::<SolSignRequest>(DERIVATION_PATH))?;
         let address = map
             .get_by_integer(ADDRESS)
             .map(|v| v.get_bytes())
-            .transpose()?;
+            .transpose()
+            .map_err(log_field_error::<SolSignRequest>(ADDRESS))?;
         let origin = map
             .get_by_integer(ORIGIN)
             .map(|v| v.get_text())
-            .transpose()?;
+            .transpose()
+            .map_err(log_field_error::<SolSignRequest>(ORIGIN))?;
         let sign_type = map.get_by_integer(SIGN_TYPE)
             .map_or(Err("sign_type is required for sol-sign-request".to_string()), |v| v.get_integer())
             .and_then(|v| match v {
                 1 => Ok(SignType::Transaction),
                 2 => Ok(SignType::Message),
                 x => Err(format!("invalid value for sign_type in sol-sign-request, expected 1 or 2, received {:?}", x)),
-            })?;
+            })
+            .map_err(log_field_error::<SolSignRequest>(SIGN_TYPE))?;
         Ok(SolSignRequest {
             request_id,
             sign_data,
@@ -202,10 +218,7 @@ impl From<SolSignRequest> for SolSignRequest {
     }
 
     fn from_bytes(bytes: Vec<u8>) -> Result<SolSignRequest, String> {
-        let value: Value = match from_slice(bytes.as_slice()) {
-            Ok(x) => x,
-            Err(e) => return Err(e.to_string()),
-        };
+        let value = decode(bytes.as_slice())?;
         SolSignRequest::from_cbor(value)
     }
 }
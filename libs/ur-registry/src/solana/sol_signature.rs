@@ -1,8 +1,8 @@
-use crate::cbor_value::CborValue;
+use crate::cbor_value::{log_decode_error, log_field_error, CborValue};
 use crate::registry_types::{RegistryType, SOL_SIGNATURE, UUID};
 use crate::traits::{From, RegistryItem, To};
 use crate::types::{Bytes, CborMap};
-use serde_cbor::{from_slice, to_vec, Value};
+use crate::cbor::{decode, encode, Value};
 
 const REQUEST_ID: i128 = 1;
 const SIGNATURE: i128 = 2;
@@ -62,22 +62,26 @@ impl To for SolSignature {
 
     fn to_bytes(&self) -> Vec<u8> {
         let value = self.to_cbor();
-        to_vec(&value).unwrap()
+        encode(&value).unwrap()
     }
 }
 
 impl From<SolSignature> for SolSignature {
     fn from_cbor(cbor: Value) -> Result<SolSignature, String> {
         let cbor_value = CborValue::new(cbor);
-        let map = cbor_value.get_map()?;
+        let map = cbor_value.get_map().map_err(log_decode_error::<SolSignature>)?;
         let request_id = map
             .get_by_integer(REQUEST_ID)
             .map(|v| v.get_tag(UUID.get_tag()).and_then(|v| v.get_bytes()))
-            .transpose()?;
-        let signature = map.get_by_integer(SIGNATURE).map_or(
-            Err("signature is required for sol-signature".to_string()),
-            |r| r.get_bytes(),
-        )?;
+            .transpose()
+            .map_err(log_field_error::<SolSignature>(REQUEST_ID))?;
+        let signature = map
+            .get_by_integer(SIGNATURE)
+            .map_or(
+                Err("signature is required for sol-signature".to_string()),
+                |r| r.get_bytes(),
+            )
+            .map_err(log_field_error::<SolSignature>(SIGNATURE))?;
         Ok(SolSignature {
             request_id,
             signature,
@@ -85,10 +89,7 @@ impl From<SolSignature> for SolSignature {
     }
 
     fn from_bytes(bytes: Vec<u8>) -> Result<SolSignature, String> {
-        let value: Value = match from_slice(bytes.as_slice()) {
-            Ok(x) => x,
-            Err(e) => return Err(e.to_string()),
-        };
+        let value = decode(bytes.as_slice())?;
         SolSignature::from_cbor(value)
     }
 }
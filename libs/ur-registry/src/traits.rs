@@ -1,25 +1,80 @@
 use crate::registry_types::RegistryType;
-use serde_cbor::Value;
+use crate::cbor::{check_duplicate_keys, decode, decode_with_max_depth, encode, encode_to_writer, Value};
+use crate::cbor_value::CborValue;
+use crate::ur_encoder::{ur_encoder, UrEncodeOptions};
 use ur::Encoder;
 
-pub trait From<T> {
+// every decodable item is a registry item, so generic decoders can check the ur type against `T`
+pub trait From<T>: RegistryItem {
     fn from_cbor(cbor: Value) -> Result<T, String>;
     fn from_bytes(bytes: Vec<u8>) -> Result<T, String>;
+
+    // rejects maps with a repeated key, which `from_bytes` resolves by keeping the last value
+    fn from_bytes_strict(bytes: Vec<u8>) -> Result<T, String> {
+        check_duplicate_keys(&bytes)?;
+        Self::from_bytes(bytes)
+    }
+
+    // `from_bytes` allows up to `MAX_DEPTH` levels of nesting, callers with a small stack can pass
+    // a lower limit, it is checked on the raw bytes before anything is decoded
+    fn from_bytes_with_max_depth(bytes: Vec<u8>, max_depth: usize) -> Result<T, String> {
+        let value = decode_with_max_depth(&bytes, max_depth)?;
+        Self::from_cbor(value)
+    }
 }
 
 pub trait To {
     fn to_cbor(&self) -> Value;
     fn to_bytes(&self) -> Vec<u8>;
+
+    // appends to the buffer so its capacity can be reused across many encodes
+    fn to_bytes_into(&self, buf: &mut Vec<u8>) {
+        encode_to_writer(buf, &self.to_cbor()).unwrap()
+    }
+
+    // for callers that inspect the value and transmit the bytes, the value is only built once
+    fn to_cbor_and_bytes(&self) -> (Value, Vec<u8>) {
+        let value = self.to_cbor();
+        let bytes = encode(&value).unwrap();
+        (value, bytes)
+    }
+}
+
+// `to_bytes` emits the bare map, this emits the self-describing form with the type's tag in front
+pub trait ToTagged {
+    fn to_cbor_tagged(&self) -> Value;
+    fn to_bytes_tagged(&self) -> Vec<u8>;
+}
+
+// counterpart of `ToTagged`, the outer tag has to match the type's registry tag
+pub trait FromTagged<T> {
+    fn from_cbor_tagged(cbor: Value) -> Result<T, String>;
+    fn from_bytes_tagged(bytes: Vec<u8>) -> Result<T, String>;
 }
 
 pub trait UR {
     fn to_ur_encoder(&self, max_fragment_length: usize) -> ur::Encoder;
+    fn to_ur_encoder_with_options(&self, options: &UrEncodeOptions) -> Result<ur::Encoder, String>;
+}
+
+// the requester shown to the user, e.g. "MetaMask". the map key is fixed by each request's spec
+// (7 in eth-sign-request, 5 in sol-sign-request) so only the accessor is shared
+pub trait RequestOrigin {
+    fn get_origin(&self) -> Option<String>;
 }
 
 pub trait RegistryItem {
     fn get_registry_type() -> RegistryType<'static>;
 }
 
+// `get_registry_type` has no receiver, this reports the type through a `&dyn RegistryTypeString`
+pub trait RegistryTypeString {
+    fn registry_type_string(&self) -> &'static str;
+}
+
+// object-safe, a `Vec<Box<dyn RegistryEncodable>>` of mixed types can be encoded uniformly
+pub trait RegistryEncodable: To + RegistryTypeString {}
+
 impl<N> UR for N
 where
     N: To + RegistryItem,
@@ -33,4 +88,191 @@ where
         )
         .unwrap()
     }
+
+    fn to_ur_encoder_with_options(&self, options: &UrEncodeOptions) -> Result<Encoder, String> {
+        ur_encoder(N::get_registry_type().get_type_str(), &self.to_bytes(), options)
+    }
+}
+
+impl<N> RegistryTypeString for N
+where
+    N: RegistryItem,
+{
+    fn registry_type_string(&self) -> &'static str {
+        N::get_registry_type().get_type_str()
+    }
+}
+
+impl<N> RegistryEncodable for N where N: To + RegistryItem {}
+
+impl<N> ToTagged for N
+where
+    N: To + RegistryItem,
+{
+    fn to_cbor_tagged(&self) -> Value {
+        Value::Tag(N::get_registry_type().get_tag(), Box::new(self.to_cbor()))
+    }
+
+    fn to_bytes_tagged(&self) -> Vec<u8> {
+        encode(&self.to_cbor_tagged()).unwrap()
+    }
+}
+
+impl<N> FromTagged<N> for N
+where
+    N: From<N> + RegistryItem,
+{
+    fn from_cbor_tagged(cbor: Value) -> Result<N, String> {
+        let value = CborValue::new(cbor).get_tag(N::get_registry_type().get_tag())?;
+        N::from_cbor(value.get_value().clone())
+    }
+
+    fn from_bytes_tagged(bytes: Vec<u8>) -> Result<N, String> {
+        let value = decode(bytes.as_slice())?;
+        N::from_cbor_tagged(value)
+    }
+}
+
+// `From` can't be a blanket `TryFrom` because of the std blanket impl, so each registry type gets
+// its own delegating impl for `value.try_into()`
+macro_rules! impl_try_from_value {
+    ($($registry_item:ty),*) => {
+        $(
+            impl TryFrom<Value> for $registry_item {
+                type Error = String;
+
+                fn try_from(value: Value) -> Result<Self, Self::Error> {
+                    <$registry_item as From<$registry_item>>::from_cbor(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_value!(
+    crate::crypto_account::CryptoAccount,
+    crate::crypto_coin_info::CryptoCoinInfo,
+    crate::crypto_ec_key::CryptoECKey,
+    crate::crypto_hd_key::CryptoHDKey,
+    crate::crypto_key_path::CryptoKeyPath,
+    crate::crypto_output::CryptoOutput,
+    crate::crypto_psbt::CryptoPSBT,
+    crate::extend::crypto_multi_accounts::CryptoMultiAccounts,
+    crate::ethereum::eth_sign_request::EthSignRequest,
+    crate::ethereum::eth_signature::EthSignature,
+    crate::solana::sol_sign_request::SolSignRequest,
+    crate::solana::sol_signature::SolSignature
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto_coin_info::{CoinType, CryptoCoinInfo, Network};
+    use crate::crypto_key_path::CryptoKeyPath;
+    use crate::crypto_hd_key::{CryptoHDKey, TEST_HD_KEY_HEX};
+    use crate::traits::{From, FromTagged, RegistryEncodable, RegistryTypeString, To, ToTagged};
+    use hex::FromHex;
+
+    #[test]
+    fn test_to_bytes_into() {
+        let coin_info = CryptoCoinInfo::new(Some(CoinType::Bitcoin), Some(Network::TestNet));
+        let mut buf = Vec::with_capacity(64);
+        buf.push(0xff);
+        coin_info.to_bytes_into(&mut buf);
+        coin_info.to_bytes_into(&mut buf);
+        let mut expected = vec![0xff];
+        expected.extend(coin_info.to_bytes());
+        expected.extend(coin_info.to_bytes());
+        assert_eq!(expected, buf);
+    }
+
+    #[test]
+    fn test_to_bytes_tagged() {
+        let coin_info = CryptoCoinInfo::new(Some(CoinType::Bitcoin), Some(Network::TestNet));
+        assert_eq!("a201000201", hex::encode(coin_info.to_bytes()));
+        assert_eq!("d90131a201000201", hex::encode(coin_info.to_bytes_tagged()));
+    }
+
+    #[test]
+    fn test_from_bytes_tagged() {
+        let coin_info = CryptoCoinInfo::new(Some(CoinType::Bitcoin), Some(Network::TestNet));
+        let decoded = CryptoCoinInfo::from_bytes_tagged(coin_info.to_bytes_tagged()).unwrap();
+        assert_eq!(Network::TestNet, decoded.get_network());
+        assert_eq!(CoinType::Bitcoin, decoded.get_coin_type());
+        assert!(CryptoCoinInfo::from_bytes_tagged(Vec::from_hex("d90131a201000201").unwrap()).is_ok());
+        assert!(CryptoCoinInfo::from_bytes_tagged(Vec::from_hex("a201000201").unwrap()).is_err());
+        assert_eq!(
+            "unexpected tag when decoding Value::Tag: received: 305, expected: 304",
+            CryptoKeyPath::from_bytes_tagged(Vec::from_hex("d90131a201000201").unwrap()).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_from_bytes_with_max_depth() {
+        // map -> tag -> map -> components array is the deepest part of the spec hdkey
+        let bytes = Vec::from_hex(TEST_HD_KEY_HEX).unwrap();
+        assert!(CryptoHDKey::from_bytes_with_max_depth(bytes.clone(), 4).is_ok());
+        assert_eq!(
+            "cbor nesting exceeds the maximum depth",
+            CryptoHDKey::from_bytes_with_max_depth(bytes, 3).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_try_into() {
+        let value = crate::cbor::decode(&Vec::from_hex("a201000201").unwrap()).unwrap();
+        let coin_info: CryptoCoinInfo = value.try_into().unwrap();
+        assert_eq!(Network::TestNet, coin_info.get_network());
+
+        let value = crate::cbor::decode(&Vec::from_hex("01").unwrap()).unwrap();
+        let result: Result<CryptoKeyPath, String> = value.try_into();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_registry_type_string() {
+        let items: Vec<Box<dyn RegistryTypeString>> = vec![
+            Box::new(CryptoHDKey::new_master_key(vec![0; 33], vec![0; 32])),
+            Box::new(CryptoCoinInfo::new(None, None)),
+        ];
+        assert_eq!("crypto-hdkey", items[0].registry_type_string());
+        assert_eq!("crypto-coin-info", items[1].registry_type_string());
+    }
+
+    #[test]
+    fn test_registry_encodable() {
+        let hd_key = CryptoHDKey::new_master_key(vec![0; 33], vec![0; 32]);
+        let coin_info = CryptoCoinInfo::new(Some(CoinType::Bitcoin), Some(Network::TestNet));
+        let items: Vec<Box<dyn RegistryEncodable>> =
+            vec![Box::new(hd_key.clone()), Box::new(coin_info.clone())];
+        let encoded = items
+            .iter()
+            .map(|item| (item.registry_type_string(), item.to_bytes()))
+            .collect::<Vec<(&str, Vec<u8>)>>();
+        assert_eq!(
+            vec![
+                ("crypto-hdkey", hd_key.to_bytes()),
+                ("crypto-coin-info", coin_info.to_bytes())
+            ],
+            encoded
+        );
+        assert_eq!("a201000201", hex::encode(&encoded[1].1));
+    }
+
+    #[test]
+    fn test_to_cbor_and_bytes() {
+        let coin_info = CryptoCoinInfo::new(Some(CoinType::Bitcoin), Some(Network::TestNet));
+        let (value, bytes) = coin_info.to_cbor_and_bytes();
+        assert_eq!(coin_info.to_cbor(), value);
+        assert_eq!(coin_info.to_bytes(), bytes);
+        assert_eq!(value, crate::cbor::decode(&bytes).unwrap());
+    }
+
+    #[test]
+    fn test_from_registry_type() {
+        fn expected_type<T: From<T>>() -> String {
+            T::get_registry_type().get_type()
+        }
+        assert_eq!("crypto-hdkey", expected_type::<CryptoHDKey>());
+        assert_eq!("crypto-coin-info", expected_type::<CryptoCoinInfo>());
+    }
 }
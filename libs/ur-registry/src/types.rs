@@ -1,4 +1,4 @@
-use serde_cbor::Value;
+use crate::cbor::Value;
 use std::collections::BTreeMap;
 
 pub type Bytes = Vec<u8>;
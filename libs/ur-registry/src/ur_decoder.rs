@@ -0,0 +1,453 @@
+use crate::traits::{From, RegistryItem, To};
+use std::collections::HashSet;
+
+const UR_SCHEME: &str = "ur:";
+
+#[derive(Clone, Debug)]
+pub struct ParsedUR {
+    ur_type: String,
+    sequence: Option<u32>,
+    sequence_count: Option<u32>,
+    payload: String,
+}
+
+impl ParsedUR {
+    pub fn parse(ur: &str) -> Result<ParsedUR, String> {
+        // QR alphanumeric mode delivers URs in uppercase, the scheme, type and bytewords are all case-insensitive
+        let ur = percent_decode(ur)?.to_ascii_lowercase();
+        let without_scheme = ur
+            .strip_prefix(UR_SCHEME)
+            .ok_or(format!("invalid scheme for ur, expected \"{}\": {}", UR_SCHEME, ur))?;
+        let (ur_type, rest) = without_scheme
+            .split_once('/')
+            .ok_or("ur type is missing".to_string())?;
+        validate_type(ur_type)?;
+        let (sequence, sequence_count, payload) = match rest.rsplit_once('/') {
+            Some((sequence, payload)) => {
+                let (index, count) = parse_sequence(sequence)?;
+                (Some(index), Some(count), payload)
+            }
+            None => (None, None, rest),
+        };
+        if payload.is_empty() {
+            return Err("ur payload is missing".to_string());
+        }
+        Ok(ParsedUR {
+            ur_type: ur_type.to_string(),
+            sequence,
+            sequence_count,
+            payload: payload.to_string(),
+        })
+    }
+
+    pub fn get_type(&self) -> String {
+        self.ur_type.clone()
+    }
+    pub fn get_sequence(&self) -> Option<u32> {
+        self.sequence
+    }
+    pub fn get_sequence_count(&self) -> Option<u32> {
+        self.sequence_count
+    }
+    pub fn get_payload(&self) -> String {
+        self.payload.clone()
+    }
+    pub fn is_multi_part(&self) -> bool {
+        self.sequence.is_some()
+    }
+
+    pub fn get_ur(&self) -> String {
+        match (self.sequence, self.sequence_count) {
+            (Some(sequence), Some(count)) => format!(
+                "{}{}/{}-{}/{}",
+                UR_SCHEME, self.ur_type, sequence, count, self.payload
+            ),
+            _ => format!("{}{}/{}", UR_SCHEME, self.ur_type, self.payload),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct URDecoder {
+    decoder: ur::Decoder,
+    ur_type: Option<String>,
+    received_part_indexes: HashSet<usize>,
+    expected_part_count: Option<usize>,
+    // opened on the first received part and kept for the rest of the scanning session
+    #[cfg(feature = "tracing")]
+    span: Option<tracing::Span>,
+}
+
+impl URDecoder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // some QR libraries strip the scheme, a multi-part fragment without it is still accepted
+    pub fn receive(&mut self, part: &str) -> Result<(), String> {
+        let parsed = match ParsedUR::parse(part) {
+            Err(e) if !has_scheme(part) => ParsedUR::parse(&format!("{}{}", UR_SCHEME, part))
+                .ok()
+                .filter(|parsed| parsed.is_multi_part())
+                .ok_or(e)?,
+            result => result?,
+        };
+        match &self.ur_type {
+            Some(ur_type) if *ur_type != parsed.get_type() => {
+                return Err(format!(
+                    "unexpected ur type, received: {}, expected: {}",
+                    parsed.get_type(),
+                    ur_type
+                ))
+            }
+            _ => {}
+        }
+        self.decoder
+            .receive(&parsed.get_ur())
+            .map_err(|e| e.to_string())?;
+        self.ur_type = Some(parsed.get_type());
+        if let (Some(sequence), Some(count)) = (parsed.get_sequence(), parsed.get_sequence_count()) {
+            // parts past the count mix several fragments, only the pure ones count towards progress
+            if sequence <= count {
+                self.received_part_indexes.insert((sequence - 1) as usize);
+            }
+            self.expected_part_count = Some(count as usize);
+        }
+        #[cfg(feature = "tracing")]
+        self.span
+            .get_or_insert_with(|| tracing::debug_span!("ur_decoder", ur_type = %parsed.get_type()))
+            .in_scope(|| {
+                tracing::debug!(
+                    sequence = ?parsed.get_sequence(),
+                    received = self.received_part_indexes.len(),
+                    expected = ?self.expected_part_count,
+                    "received ur part"
+                );
+                if self.decoder.complete() {
+                    tracing::debug!("ur decoding complete");
+                }
+            });
+        Ok(())
+    }
+
+    pub fn get_type(&self) -> Option<String> {
+        self.ur_type.clone()
+    }
+
+    // zero-based sequence numbers of the distinct pure fragments received so far, mixed fountain
+    // parts are not included so the count never exceeds `expected_part_count`
+    pub fn received_part_indexes(&self) -> &HashSet<usize> {
+        &self.received_part_indexes
+    }
+
+    pub fn expected_part_count(&self) -> Option<usize> {
+        self.expected_part_count
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.decoder.complete()
+    }
+
+    pub fn message(&self) -> Result<Option<Vec<u8>>, String> {
+        self.decoder.message().map_err(|e| e.to_string())
+    }
+}
+
+// the UR spec only allows lowercase letters, digits and hyphens in the type
+pub fn validate_type(ur_type: &str) -> Result<(), String> {
+    if ur_type.is_empty() {
+        return Err("ur type is empty".to_string());
+    }
+    match ur_type
+        .chars()
+        .find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '-'))
+    {
+        Some(c) => Err(format!(
+            "invalid character {:?} in ur type {}, expected lowercase letters, digits or hyphens",
+            c, ur_type
+        )),
+        None => Ok(()),
+    }
+}
+
+// returns the type and raw cbor of a single-part ur
+pub fn ur_payload(ur: &str) -> Result<(String, Vec<u8>), String> {
+    let parsed = ParsedUR::parse(ur)?;
+    if parsed.is_multi_part() {
+        return Err("multi-part ur is not supported, use URDecoder instead".to_string());
+    }
+    let payload = ur::bytewords::decode(&parsed.get_payload(), ur::bytewords::Style::Minimal)
+        .map_err(|e| e.to_string())?;
+    Ok((parsed.get_type(), payload))
+}
+
+// the whole item as a single-part ur, for payloads small enough to fit one QR code
+pub fn encode_ur<T: To + RegistryItem>(item: &T) -> String {
+    format!(
+        "{}{}/{}",
+        UR_SCHEME,
+        T::get_registry_type().get_type(),
+        ur::bytewords::encode(&item.to_bytes(), ur::bytewords::Style::Minimal)
+    )
+}
+
+// inverse of `encode_ur`, the ur type has to be the registry type of `T`
+pub fn decode_ur<T: From<T>>(ur: &str) -> Result<T, String> {
+    let (ur_type, payload) = ur_payload(ur)?;
+    let expected = T::get_registry_type().get_type();
+    if ur_type != expected {
+        return Err(format!(
+            "unexpected ur type: received: {}, expected: {}",
+            ur_type, expected
+        ));
+    }
+    T::from_bytes(payload)
+}
+
+fn has_scheme(ur: &str) -> bool {
+    ur.get(..UR_SCHEME.len())
+        .map_or(false, |scheme| scheme.eq_ignore_ascii_case(UR_SCHEME))
+}
+
+// URs passed through deep links may arrive percent-encoded
+fn percent_decode(ur: &str) -> Result<String, String> {
+    if !ur.contains('%') {
+        return Ok(ur.to_string());
+    }
+    let bytes = ur.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' => {
+                let byte = bytes
+                    .get(index + 1..index + 3)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or(format!("invalid percent encoding in ur: {}", ur))?;
+                decoded.push(byte);
+                index += 3;
+            }
+            x => {
+                decoded.push(x);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| format!("invalid percent encoding in ur: {}", ur))
+}
+
+fn parse_sequence(sequence: &str) -> Result<(u32, u32), String> {
+    let (index, count) = sequence
+        .split_once('-')
+        .ok_or(format!("invalid sequence component in ur: {}", sequence))?;
+    let index = index
+        .parse::<u32>()
+        .map_err(|_| format!("invalid sequence number in ur: {}", sequence))?;
+    let count = count
+        .parse::<u32>()
+        .map_err(|_| format!("invalid sequence count in ur: {}", sequence))?;
+    if index == 0 || count == 0 {
+        return Err(format!("invalid sequence component in ur: {}", sequence));
+    }
+    Ok((index, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto_hd_key::{CryptoHDKey, TEST_HD_KEY_HEX};
+    use crate::traits::{From, To, UR};
+    use crate::crypto_coin_info::{CoinType, CryptoCoinInfo, Network};
+    use crate::ur_decoder::{decode_ur, encode_ur, ur_payload, validate_type, ParsedUR, URDecoder};
+    use hex::FromHex;
+
+    #[test]
+    fn test_parse() {
+        let parsed = ParsedUR::parse("ur:crypto-hdkey/1-1/lpadadcsiocyihbdaehnhdioonaxhdclaojlvoechgferkdpqdiabdrflawshlhdmdcemtfnlrctghchbdolvwsednvdztbgolaahdcxtottgostdkhfdahdlykkecbbweskrymwflvdylgerkloswtbrpfdbsticmwylklpahtaadehoyaoadamtaaddyoyadlecsdwykadykadykaewkadwkaycywlcscewfcpghbziy").unwrap();
+        assert_eq!("crypto-hdkey", parsed.get_type());
+        assert_eq!(true, parsed.is_multi_part());
+        assert_eq!(Some(1), parsed.get_sequence());
+        assert_eq!(Some(1), parsed.get_sequence_count());
+
+        let parsed = ParsedUR::parse("ur:eth-sign-request/oeaoykaxhdcxlkahssqzwfvslofzoxwkrewngotktbmwjkwdcmnefsaaehrlolkskncnktlbaypkrphsmyid").unwrap();
+        assert_eq!("eth-sign-request", parsed.get_type());
+        assert_eq!(false, parsed.is_multi_part());
+        assert_eq!(
+            "oeaoykaxhdcxlkahssqzwfvslofzoxwkrewngotktbmwjkwdcmnefsaaehrlolkskncnktlbaypkrphsmyid",
+            parsed.get_payload()
+        );
+    }
+
+    #[test]
+    fn test_validate_type() {
+        assert!(validate_type("crypto-hdkey").is_ok());
+        assert!(validate_type("eth-sign-request").is_ok());
+        assert!(validate_type("bytes2").is_ok());
+        assert!(validate_type("").is_err());
+        assert_eq!(
+            "invalid character 'C' in ur type Crypto_HDKey, expected lowercase letters, digits or hyphens",
+            validate_type("Crypto_HDKey").unwrap_err()
+        );
+        assert!(ParsedUR::parse("ur:Crypto_HDKey/oeaoykaxhdcx").is_err());
+        assert!(ParsedUR::parse("crypto-hdkey/oeaoykaxhdcx").is_err());
+        assert!(ParsedUR::parse("ur:crypto-hdkey/0-1/oeaoykaxhdcx").is_err());
+    }
+
+    #[test]
+    fn test_parse_percent_encoded() {
+        let parsed = ParsedUR::parse("ur%3Aeth-sign-request%2Foeaoykaxhdcxlkahssqzwfvslofzoxwkrewngotktbmwjkwdcmnefsaaehrlolkskncnktlbaypkrphsmyid").unwrap();
+        assert_eq!("eth-sign-request", parsed.get_type());
+        assert_eq!(
+            "ur:eth-sign-request/oeaoykaxhdcxlkahssqzwfvslofzoxwkrewngotktbmwjkwdcmnefsaaehrlolkskncnktlbaypkrphsmyid",
+            parsed.get_ur()
+        );
+        assert!(ParsedUR::parse("ur%3Aeth-sign-request%2").is_err());
+        assert!(ParsedUR::parse("ur%zzeth-sign-request/oeaoykaxhdcx").is_err());
+    }
+
+    #[test]
+    fn test_parse_uppercase() {
+        let parsed = ParsedUR::parse("UR:ETH-SIGN-REQUEST/OEAOYKAXHDCXLKAHSSQZWFVSLOFZOXWKREWNGOTKTBMWJKWDCMNEFSAAEHRLOLKSKNCNKTLBAYPKRPHSMYID").unwrap();
+        assert_eq!("eth-sign-request", parsed.get_type());
+        assert_eq!(
+            "oeaoykaxhdcxlkahssqzwfvslofzoxwkrewngotktbmwjkwdcmnefsaaehrlolkskncnktlbaypkrphsmyid",
+            parsed.get_payload()
+        );
+        let parsed = ParsedUR::parse("UR:CRYPTO-HDKEY/1-3/LPADAXCSIOCYIHBDAEHNHDIOONAXHD").unwrap();
+        assert_eq!("crypto-hdkey", parsed.get_type());
+        assert_eq!(Some(3), parsed.get_sequence_count());
+    }
+
+    #[test]
+    fn test_decoder_progress() {
+        let hd_key = CryptoHDKey::from_bytes(Vec::from_hex(TEST_HD_KEY_HEX).unwrap()).unwrap();
+        let mut encoder = hd_key.to_ur_encoder(20);
+        let mut decoder = URDecoder::new();
+        assert_eq!(None, decoder.expected_part_count());
+        assert_eq!(0, decoder.received_part_indexes().len());
+
+        while !decoder.is_complete() {
+            decoder.receive(&encoder.next_part().unwrap()).unwrap();
+            assert!(decoder.received_part_indexes().len() <= decoder.expected_part_count().unwrap());
+        }
+        assert_eq!(Some("crypto-hdkey".to_string()), decoder.get_type());
+        assert!(decoder.expected_part_count().unwrap() > 1);
+        assert_eq!(hd_key.to_bytes(), decoder.message().unwrap().unwrap());
+
+        // a mixed part past the pure fragments isn't counted
+        let mut encoder = hd_key.to_ur_encoder(20);
+        let count = ParsedUR::parse(&encoder.next_part().unwrap()).unwrap().get_sequence_count().unwrap();
+        let mixed = (0..count).map(|_| encoder.next_part().unwrap()).last().unwrap();
+        assert_eq!(Some(count + 1), ParsedUR::parse(&mixed).unwrap().get_sequence());
+        let mut decoder = URDecoder::new();
+        decoder.receive(&mixed).unwrap();
+        assert_eq!(0, decoder.received_part_indexes().len());
+        assert_eq!(Some(count as usize), decoder.expected_part_count());
+    }
+
+    #[test]
+    fn test_decoder_without_scheme() {
+        let hd_key = CryptoHDKey::from_bytes(Vec::from_hex(TEST_HD_KEY_HEX).unwrap()).unwrap();
+        let mut encoder = hd_key.to_ur_encoder(20);
+        let mut decoder = URDecoder::new();
+        while !decoder.is_complete() {
+            let part = encoder.next_part().unwrap();
+            decoder.receive(part.strip_prefix("ur:").unwrap()).unwrap();
+        }
+        assert_eq!(hd_key.to_bytes(), decoder.message().unwrap().unwrap());
+
+        let mut decoder = URDecoder::new();
+        assert!(decoder.receive("crypto-hdkey/oeaoykaxhdcx").is_err());
+    }
+
+    #[test]
+    fn test_ur_payload() {
+        let (ur_type, payload) = ur_payload("ur:crypto-hdkey/onaxhdclaojlvoechgferkdpqdiabdrflawshlhdmdcemtfnlrctghchbdolvwsednvdztbgolaahdcxtottgostdkhfdahdlykkecbbweskrymwflvdylgerkloswtbrpfdbsticmwylklpahtaadehoyaoadamtaaddyoyadlecsdwykadykadykaewkadwkaycywlcscewfihbdaehn").unwrap();
+        assert_eq!("crypto-hdkey", ur_type);
+        assert_eq!(
+            TEST_HD_KEY_HEX,
+            hex::encode(&payload).to_uppercase()
+        );
+        assert!(CryptoHDKey::from_bytes(payload).is_ok());
+        assert!(ur_payload("ur:crypto-hdkey/1-1/lpadadcsiocyihbdaehnhdioonaxhdclaojlvoechgferkdpqdiabdrflawshlhdmdcemtfnlrctghchbdolvwsednvdztbgolaahdcxtottgostdkhfdahdlykkecbbweskrymwflvdylgerkloswtbrpfdbsticmwylklpahtaadehoyaoadamtaaddyoyadlecsdwykadykadykaewkadwkaycywlcscewfcpghbziy").is_err());
+        assert!(ur_payload("ur:crypto-hdkey/onaxhdclaojlvoechgfe").is_err());
+        // an empty message is only the checksum of nothing
+        assert_eq!(Vec::<u8>::new(), ur_payload("ur:bytes/aeaeaeae").unwrap().1);
+    }
+
+    #[test]
+    fn test_encode_ur() {
+        let hd_key = CryptoHDKey::from_bytes(Vec::from_hex(TEST_HD_KEY_HEX).unwrap()).unwrap();
+        let ur = encode_ur(&hd_key);
+        assert_eq!(
+            "ur:crypto-hdkey/onaxhdclaojlvoechgferkdpqdiabdrflawshlhdmdcemtfnlrctghchbdolvwsednvdztbgolaahdcxtottgostdkhfdahdlykkecbbweskrymwflvdylgerkloswtbrpfdbsticmwylklpahtaadehoyaoadamtaaddyoyadlecsdwykadykadykaewkadwkaycywlcscewfihbdaehn",
+            ur
+        );
+        assert_eq!(hd_key.to_bytes(), ur_payload(&ur).unwrap().1);
+    }
+
+    #[test]
+    fn test_decode_ur() {
+        let hd_key: CryptoHDKey = decode_ur("ur:crypto-hdkey/onaxhdclaojlvoechgferkdpqdiabdrflawshlhdmdcemtfnlrctghchbdolvwsednvdztbgolaahdcxtottgostdkhfdahdlykkecbbweskrymwflvdylgerkloswtbrpfdbsticmwylklpahtaadehoyaoadamtaaddyoyadlecsdwykadykadykaewkadwkaycywlcscewfihbdaehn").unwrap();
+        assert_eq!(
+            "026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6",
+            hex::encode(hd_key.get_key())
+        );
+
+        let coin_info = CryptoCoinInfo::new(Some(CoinType::Bitcoin), Some(Network::TestNet));
+        let ur = encode_ur(&coin_info);
+        assert_eq!(Network::TestNet, decode_ur::<CryptoCoinInfo>(&ur).unwrap().get_network());
+        // rejected by the type check before the coin-info map reaches the hdkey parser
+        assert_eq!(
+            "unexpected ur type: received: crypto-coin-info, expected: crypto-hdkey",
+            decode_ur::<CryptoHDKey>(&ur).unwrap_err()
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_span_per_session() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::{Arc, Mutex};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata};
+
+        #[derive(Clone, Default)]
+        struct SpanCapture {
+            spans: Arc<Mutex<Vec<String>>>,
+            events: Arc<Mutex<Vec<String>>>,
+            next_id: Arc<AtomicU64>,
+        }
+        impl tracing::Subscriber for SpanCapture {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                self.spans.lock().unwrap().push(span.metadata().name().to_string());
+                Id::from_u64(self.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                self.events.lock().unwrap().push(event.metadata().name().to_string());
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let hd_key = CryptoHDKey::from_bytes(Vec::from_hex(TEST_HD_KEY_HEX).unwrap()).unwrap();
+        let capture = SpanCapture::default();
+        tracing::subscriber::with_default(capture.clone(), || {
+            for _ in 0..2 {
+                let mut encoder = hd_key.to_ur_encoder(20);
+                let mut decoder = URDecoder::new();
+                while !decoder.is_complete() {
+                    decoder.receive(&encoder.next_part().unwrap()).unwrap();
+                }
+            }
+        });
+        assert_eq!(vec!["ur_decoder", "ur_decoder"], *capture.spans.lock().unwrap());
+        assert!(capture.events.lock().unwrap().len() > 2);
+    }
+}
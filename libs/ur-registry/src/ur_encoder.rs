@@ -0,0 +1,113 @@
+// error correction level of the QR codes the parts are shown in, denser levels leave less room per part
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QrErrorCorrection {
+    Low,
+    Medium,
+    Quartile,
+    High,
+}
+
+// tuning for multi-part urs. `qr_ec` is not used by the encoder, it is carried for the QR renderer
+#[derive(Clone, Debug, PartialEq)]
+pub struct UrEncodeOptions {
+    pub max_fragment_len: usize,
+    pub min_fragment_len: usize,
+    pub qr_ec: QrErrorCorrection,
+}
+
+impl Default for UrEncodeOptions {
+    fn default() -> Self {
+        UrEncodeOptions {
+            max_fragment_len: 200,
+            min_fragment_len: 10,
+            qr_ec: QrErrorCorrection::Low,
+        }
+    }
+}
+
+impl UrEncodeOptions {
+    // the fewest equal fragments no longer than `max_fragment_len`, without going below
+    // `min_fragment_len` per fragment, as in the reference fountain encoder
+    pub fn fragment_len(&self, message_len: usize) -> Result<usize, String> {
+        if self.min_fragment_len == 0 || self.min_fragment_len > self.max_fragment_len {
+            return Err(format!(
+                "invalid fragment lengths, expected 0 < min ({}) <= max ({})",
+                self.min_fragment_len, self.max_fragment_len
+            ));
+        }
+        let max_fragment_count = (message_len / self.min_fragment_len).max(1);
+        let fragment_len = |count: usize| (message_len + count - 1) / count;
+        Ok((1..=max_fragment_count)
+            .map(fragment_len)
+            .find(|len| *len <= self.max_fragment_len)
+            .unwrap_or(fragment_len(max_fragment_count)))
+    }
+}
+
+// encodes a raw message, e.g. the type and bytes of a finished `URDecoder`, so a received payload
+// can be shown again at a different QR density without decoding it into a registry type
+pub fn ur_encoder(ur_type: &str, message: &[u8], options: &UrEncodeOptions) -> Result<ur::Encoder, String> {
+    ur::Encoder::new(message, options.fragment_len(message.len())?, ur_type).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto_hd_key::{CryptoHDKey, TEST_HD_KEY_HEX};
+    use crate::traits::{From, UR};
+    use crate::ur_decoder::{ParsedUR, URDecoder};
+    use crate::ur_encoder::{ur_encoder, UrEncodeOptions};
+    use hex::FromHex;
+
+    #[test]
+    fn test_fragment_len() {
+        let options = UrEncodeOptions::default();
+        assert_eq!(118, options.fragment_len(118).unwrap());
+        let options = UrEncodeOptions { max_fragment_len: 20, ..Default::default() };
+        assert_eq!(20, options.fragment_len(118).unwrap());
+        let options = UrEncodeOptions { max_fragment_len: 100, min_fragment_len: 60, ..Default::default() };
+        assert_eq!(100, options.fragment_len(200).unwrap());
+        // 118 bytes can't be split into more than one fragment of at least 60 bytes
+        assert_eq!(118, options.fragment_len(118).unwrap());
+        let options = UrEncodeOptions { max_fragment_len: 10, min_fragment_len: 20, ..Default::default() };
+        assert!(options.fragment_len(118).is_err());
+    }
+
+    #[test]
+    fn test_to_ur_encoder_with_options() {
+        let hd_key = CryptoHDKey::from_bytes(Vec::from_hex(TEST_HD_KEY_HEX).unwrap()).unwrap();
+        let part_count = |max_fragment_len: usize| {
+            let options = UrEncodeOptions { max_fragment_len, ..Default::default() };
+            let part = hd_key.to_ur_encoder_with_options(&options).unwrap().next_part().unwrap();
+            ParsedUR::parse(&part).unwrap().get_sequence_count()
+        };
+        // 103 bytes as 6 fragments of 18 and 2 of 52
+        assert_eq!(Some(6), part_count(20));
+        assert_eq!(Some(2), part_count(100));
+    }
+
+    #[test]
+    fn test_ur_encoder_rechunk() {
+        let hd_key = CryptoHDKey::from_bytes(Vec::from_hex(TEST_HD_KEY_HEX).unwrap()).unwrap();
+        let mut encoder = hd_key.to_ur_encoder(100);
+        let mut decoder = URDecoder::new();
+        while !decoder.is_complete() {
+            decoder.receive(&encoder.next_part().unwrap()).unwrap();
+        }
+        let ur_type = decoder.get_type().unwrap();
+        let message = decoder.message().unwrap().unwrap();
+
+        let options = UrEncodeOptions { max_fragment_len: 20, ..Default::default() };
+        let mut encoder = ur_encoder(&ur_type, &message, &options).unwrap();
+        let mut decoder = URDecoder::new();
+        let mut parts = 0;
+        while !decoder.is_complete() {
+            let part = encoder.next_part().unwrap();
+            assert_eq!(Some(6), ParsedUR::parse(&part).unwrap().get_sequence_count());
+            decoder.receive(&part).unwrap();
+            parts += 1;
+        }
+        assert!(parts >= 6);
+        assert_eq!(Some("crypto-hdkey".to_string()), decoder.get_type());
+        assert_eq!(message, decoder.message().unwrap().unwrap());
+    }
+}
@@ -0,0 +1,52 @@
+use hex::FromHex;
+use ur_registry::crypto_coin_info::CryptoCoinInfo;
+use ur_registry::crypto_ec_key::CryptoECKey;
+use ur_registry::crypto_hd_key::CryptoHDKey;
+use ur_registry::crypto_key_path::CryptoKeyPath;
+use ur_registry::extend::crypto_multi_accounts::CryptoMultiAccounts;
+use ur_registry::traits::{From, To};
+
+// run with `--features ciborium` as well, both backends must produce the same canonical bytes
+const VECTORS: &[(&str, &str)] = &[
+    ("crypto-hdkey", "a301f503582100e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35045820873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508"),
+    ("crypto-hdkey", "a5035821026fe2355745bb2db3630bbc80ef5d58951c963c841f54170ba6e5c12be7fc12a6045820ced155c72456255881793514edc5bd9447e7f74abb88c6d6b6480fd016ee8c8505d90131a1020106d90130a1018a182cf501f501f500f401f4081ae9181cf3"),
+    ("crypto-eckey", "a202f50358208c05c4b4f3e88840a4f4b5f155cfd69473ea169f3d0431b7a6787a23777f08aa"),
+    ("crypto-coin-info", "a201000201"),
+    ("crypto-keypath", "a3018a182cf501f501f500f401f4021ad34db33f0305"),
+    ("crypto-multi-accounts", "a3011ae9181cf30281d9012fa203582102eae4b876a8696134b868f88cc2f51f715f2dbedb7446b8e6edf3d4541c4eb67b06d90130a10188182cf51901f5f500f500f503686b657973746f6e65"),
+];
+
+fn roundtrip<T: From<T> + To>(bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+    Ok(T::from_bytes(bytes)?.to_bytes())
+}
+
+fn roundtrip_as(ur_type: &str, bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+    match ur_type {
+        "crypto-hdkey" => roundtrip::<CryptoHDKey>(bytes),
+        "crypto-eckey" => roundtrip::<CryptoECKey>(bytes),
+        "crypto-coin-info" => roundtrip::<CryptoCoinInfo>(bytes),
+        "crypto-keypath" => roundtrip::<CryptoKeyPath>(bytes),
+        "crypto-multi-accounts" => roundtrip::<CryptoMultiAccounts>(bytes),
+        x => panic!("no decoder for {}", x),
+    }
+}
+
+#[test]
+fn test_vectors_roundtrip() {
+    for (ur_type, vector) in VECTORS {
+        let bytes = Vec::from_hex(vector).unwrap();
+        let result = roundtrip_as(ur_type, bytes);
+        assert_eq!(*vector, hex::encode(result.unwrap()), "{}", ur_type);
+    }
+}
+
+#[test]
+fn test_vectors_trailing_bytes() {
+    // both backends have to reject data after the item instead of ignoring it
+    for (ur_type, vector) in VECTORS {
+        let mut bytes = Vec::from_hex(vector).unwrap();
+        bytes.push(0x00);
+        let result = roundtrip_as(ur_type, bytes);
+        assert!(result.is_err(), "{}", ur_type);
+    }
+}